@@ -0,0 +1,94 @@
+//! Throughput of the brain inference and of the whole agent update for 1000 agents
+//!
+//! `cargo bench --bench inference`
+
+use std::{
+    ops::Deref,
+    time::{Duration, Instant},
+};
+
+use wasm_self_driving_car::{
+    ai::{agents::Agents, NeuralNetwork},
+    car::Car,
+    road::Road,
+    scenario::Scenario,
+    traffic::Traffic,
+    utils::seed_random,
+    Config, CAR_CANVAS_WIDTH_DEFAULT, CAR_Y_DEFAULT,
+};
+
+const AGENTS: usize = 1000;
+const TICKS: usize = 1000;
+
+fn report(name: &str, elapsed: Duration, evaluations: usize) {
+    println!(
+        "{:<28} {:>10.2} ms {:>14.0} evaluations/s",
+        name,
+        elapsed.as_secs_f64() * 1000.,
+        evaluations as f64 / elapsed.as_secs_f64()
+    );
+}
+
+fn inputs(tick: usize, count: usize) -> Vec<f64> {
+    (0..count).map(|i| ((tick + i) % 10) as f64 / 10.).collect()
+}
+
+fn main() {
+    seed_random(1);
+    let config = Config {
+        cars_count: AGENTS,
+        ..Config::default()
+    };
+    let neurons = config.neurons_count();
+    let inputs = (0..TICKS)
+        .map(|tick| inputs(tick, config.rays_count))
+        .collect::<Vec<Vec<f64>>>();
+
+    let mut brains = (0..AGENTS)
+        .map(|_| NeuralNetwork::new(neurons.deref()))
+        .collect::<Vec<NeuralNetwork>>();
+
+    let start = Instant::now();
+    let mut checksum = 0.;
+    for tick_inputs in inputs.iter() {
+        for brain in brains.iter_mut() {
+            checksum += brain.feed_forward(tick_inputs)[0];
+        }
+    }
+    report("feed_forward", start.elapsed(), AGENTS * TICKS);
+
+    let start = Instant::now();
+    for tick_inputs in inputs.iter() {
+        for brain in brains.iter() {
+            checksum += brain.forward(tick_inputs)[0];
+        }
+    }
+    report("forward (allocating)", start.elapsed(), AGENTS * TICKS);
+
+    let road = Road::new(
+        CAR_CANVAS_WIDTH_DEFAULT / 2.,
+        CAR_CANVAS_WIDTH_DEFAULT * 0.9,
+        config.lanes_count as i32,
+    );
+    let mut traffic = Traffic::new();
+    Scenario::training().spawn(&road, &mut traffic, CAR_Y_DEFAULT);
+    let mut agents = Agents::new(Car::generate_cars(
+        road.lane_center(config.lane_index as i32),
+        brains,
+        &config,
+    ));
+
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        traffic.update();
+        agents.update(&road, &traffic);
+    }
+    report(
+        "agents update (with sensors)",
+        start.elapsed(),
+        AGENTS * TICKS,
+    );
+
+    // keeps the optimizer from throwing the inference away
+    println!("checksum {}", checksum);
+}
@@ -0,0 +1,40 @@
+//! Native evolution strategy trainer, evolves the brain on the training scenario and stores it as JSON
+//!
+//! `cargo run --release --example evolve -- <generations> <output file>`
+
+use std::ops::Deref;
+
+use wasm_self_driving_car::{
+    ai::{
+        es::{EvolutionStrategy, LEARNING_RATE_DEFAULT, POPULATION_DEFAULT, SIGMA_DEFAULT},
+        NeuralNetwork,
+    },
+    Config,
+};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let generations = args
+        .next()
+        .map(|g| g.parse::<usize>().expect("generations has to be a number"))
+        .unwrap_or(100);
+    let output = args
+        .next()
+        .unwrap_or_else(|| "evolved_brain.json".to_string());
+
+    let config = Config::default();
+    let mut es = EvolutionStrategy::new(
+        &NeuralNetwork::new(config.neurons_count().deref()),
+        SIGMA_DEFAULT,
+        LEARNING_RATE_DEFAULT,
+        POPULATION_DEFAULT,
+    );
+
+    for generation in 1..=generations {
+        let fitness = es.train_generation(&config);
+        println!("generation {generation}: mean brain fitness {:.1}", fitness);
+    }
+
+    std::fs::write(&output, es.mean().serialize_brain()).expect("failed to store brain");
+    println!("brain stored to {output}");
+}
@@ -0,0 +1,33 @@
+//! Native reinforcement learning trainer, trains the brain on the training scenario and stores it as JSON
+//!
+//! `cargo run --release --example reinforce -- <episodes> <output file>`
+
+use wasm_self_driving_car::{ai::rl::ReinforceTrainer, Config};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let episodes = args
+        .next()
+        .map(|e| e.parse::<usize>().expect("episodes has to be a number"))
+        .unwrap_or(500);
+    let output = args
+        .next()
+        .unwrap_or_else(|| "reinforce_brain.json".to_string());
+
+    let config = Config::default();
+    let mut trainer = ReinforceTrainer::new(&config, 0.01);
+
+    for episode in 1..=episodes {
+        let stats = trainer.train_episode();
+        if episode % 10 == 0 {
+            let greedy = trainer.evaluate(0);
+            println!(
+                "episode {episode}: fitness {:.1}, reward {:.1}, ticks {}, greedy fitness {:.1}, finished {}",
+                stats.fitness, stats.total_reward, stats.ticks, greedy.fitness, greedy.finished
+            );
+        }
+    }
+
+    std::fs::write(&output, trainer.policy().serialize_brain()).expect("failed to store brain");
+    println!("brain stored to {output}");
+}
@@ -1,12 +1,24 @@
 use std::{collections::HashMap, ops::Neg};
 
-use crate::{car::Car, error, road::Road, Traffic};
+use crate::{car::Car, controls::KeyEvent, error, road::Road, Traffic};
 use web_sys::CanvasRenderingContext2d;
 
 type AgentId = usize;
 type Index = usize;
 
-#[derive(Debug)]
+/// ID reserved for the car driven by human, it fits into `u32` so it survives trip to JS
+pub const HUMAN_AGENT_ID: AgentId = u32::MAX as AgentId;
+
+/// Score of the agent, the further the agent got the better
+///
+/// we start at y position of Y
+/// so the cars that start going backwards are considered as highest scores
+/// so we reverse their score
+pub fn fitness(car: &Car) -> f64 {
+    car.y.neg()
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 enum Focus {
     /// Follow best agent
     BestAgent,
@@ -21,12 +33,15 @@ impl Default for Focus {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Agents {
     /// Best agent should be most desired, so we will cache its index
     best_agent: (AgentId, Index),
     /// All of our agents we will operate on
     agents: Vec<Car>,
+    /// ID and last score of agents removed by [`Agents::clean`], they are no longer updated, drawn nor bred,
+    /// only their score counts, so snapshots do not have to carry their brains
+    retired: Vec<(AgentId, f64)>,
     /// Map that tracks scores of each agent
     /// `AgentId` is equivalent to `agent.id`
     scores: HashMap<AgentId, f64>,
@@ -40,11 +55,12 @@ impl Agents {
             best_agent: (0, 0),
             scores: HashMap::new(),
             agents: cars,
+            retired: vec![],
             focused_agent: Focus::default(),
         }
     }
 
-    /// Returns reference to agent with highest score
+    /// Returns reference to AI agent with highest score
     pub fn best_agent(&self) -> Option<&Car> {
         self.agents.get(self.best_agent.1)
     }
@@ -67,11 +83,9 @@ impl Agents {
             }
             car.update(road, traffic);
 
-            // we start at y position of Y
-            // so the cars that start going backwards are considered as highest scores
-            // so we reverse their score
-            let score = car.y.neg();
-            if score > tmp_score {
+            let score = fitness(car);
+            // human races the agents, but is never the best agent to follow or breed from
+            if score > tmp_score && !car.is_human() {
                 tmp_score = score;
                 best_agent = (car.id, i);
             }
@@ -83,6 +97,27 @@ impl Agents {
         self.best_agent = best_agent;
     }
 
+    /// Adds car driven by human, if there already is one, it is replaced
+    pub fn add_human(&mut self, car: Car) {
+        self.agents.retain(|c| !c.is_human());
+        self.scores.remove(&car.id);
+        self.agents.push(car);
+        self.refocus();
+    }
+
+    /// Returns reference to the car driven by human
+    pub fn human(&self) -> Option<&Car> {
+        self.agents.iter().find(|c| c.is_human())
+    }
+
+    /// Passes key input to all cars driven by human
+    pub fn handle_key_input(&mut self, event: KeyEvent) {
+        self.agents
+            .iter_mut()
+            .filter(|c| c.is_human())
+            .for_each(|c| c.handle_key_input(event));
+    }
+
     /// Draw all our agents on provided canvas
     pub fn draw(&mut self, car_ctx: &CanvasRenderingContext2d) {
         let focus_agent_index = self.focus_agent_index();
@@ -193,6 +228,31 @@ impl Agents {
         })
     }
 
+    /// AI agents still on the road, agents removed by [`Agents::clean`] are in [`Agents::retired`]
+    pub fn population(&self) -> impl Iterator<Item = &Car> {
+        self.agents.iter().filter(|c| !c.is_human())
+    }
+
+    /// ID and last score of each agent removed by [`Agents::clean`]
+    pub fn retired(&self) -> &[(AgentId, f64)] {
+        &self.retired
+    }
+
+    /// Iterator over agents still on the road
+    pub fn iter(&self) -> impl Iterator<Item = &Car> {
+        self.agents.iter()
+    }
+
+    /// Agent with the given ID, agents removed by [`Agents::clean`] are not there anymore
+    pub fn get(&self, id: AgentId) -> Option<&Car> {
+        self.agents.iter().find(|car| car.id == id)
+    }
+
+    /// Last known score of the agent, agents removed by [`Agents::clean`] keep their last score
+    pub fn score(&self, id: AgentId) -> Option<f64> {
+        self.scores.get(&id).copied()
+    }
+
     pub fn n_best(&self, count: usize) -> Vec<(AgentId, f64)> {
         let mut data = self
             .scores
@@ -206,7 +266,19 @@ impl Agents {
 
     pub fn clean(&mut self) {
         let y = self.best_agent().map(|a| a.y.abs()).unwrap_or_default();
-        self.agents.retain(|c| c.y.abs() > y.abs() - 500.);
+        // human driver is never removed, so the race can go on even after falling behind
+        let (agents, retired): (Vec<Car>, Vec<Car>) = self
+            .agents
+            .drain(..)
+            .partition(|c| c.is_human() || c.y.abs() > y.abs() - 500.);
+        self.agents = agents;
+        let scores = &self.scores;
+        self.retired.extend(retired.iter().map(|car| {
+            (
+                car.id,
+                scores.get(&car.id).copied().unwrap_or_else(|| fitness(car)),
+            )
+        }));
         // after cleaning vector, indexes will change and so our custom focused index might point to different car
         // so we need to update it
         self.refocus();
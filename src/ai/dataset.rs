@@ -0,0 +1,104 @@
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// One recorded decision, what the driver saw and which controls were pressed
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Sample {
+    /// sensor readings in the same form as they are fed to the brain
+    pub inputs: Vec<f64>,
+    /// controls in the same order as brain's outputs, ie up, left, right, down
+    pub outputs: Vec<f64>,
+}
+
+/// Recorded demonstrations used for supervised training
+#[wasm_bindgen]
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Dataset {
+    #[wasm_bindgen(skip)]
+    pub samples: Vec<Sample>,
+}
+
+#[wasm_bindgen]
+impl Dataset {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Dataset::default()
+    }
+
+    pub fn push(&mut self, inputs: Vec<f64>, outputs: Vec<f64>) {
+        self.samples.push(Sample { inputs, outputs });
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize dataset")
+    }
+
+    /// Each sample on its own line, inputs first followed by the outputs
+    ///
+    /// Header is derived from the first sample, `in_0..in_n,up,left,right,down`
+    #[wasm_bindgen(js_name = toCsv)]
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+
+        if let Some(first) = self.samples.first() {
+            let header = (0..first.inputs.len())
+                .map(|i| format!("in_{i}"))
+                .chain(
+                    ["up", "left", "right", "down"]
+                        .iter()
+                        .map(|s| s.to_string()),
+                )
+                .collect::<Vec<String>>()
+                .join(",");
+            csv.push_str(&header);
+            csv.push('\n');
+        }
+
+        for sample in self.samples.iter() {
+            let row = sample
+                .inputs
+                .iter()
+                .chain(sample.outputs.iter())
+                .map(|value| value.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+            csv.push_str(&row);
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+impl Dataset {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Checks that every sample fits brain with `inputs_count` inputs and `outputs_count` outputs,
+    /// samples of other shape would train wrong weights
+    pub fn validate(&self, inputs_count: usize, outputs_count: usize) -> Result<(), String> {
+        match self.samples.iter().enumerate().find(|(_, sample)| {
+            sample.inputs.len() != inputs_count || sample.outputs.len() != outputs_count
+        }) {
+            Some((index, sample)) => Err(format!(
+                "sample {index} has {} inputs and {} outputs, expected {inputs_count} and {outputs_count}",
+                sample.inputs.len(),
+                sample.outputs.len()
+            )),
+            None => Ok(()),
+        }
+    }
+}
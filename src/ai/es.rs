@@ -0,0 +1,161 @@
+//! Evolution strategy optimizing weights and biases of a brain
+//!
+//! Parameters of the brain are treated as one vector. Every generation a population of brains is
+//! sampled around the current mean, evaluated, and the mean is moved in the direction estimated
+//! from how well the samples did (OpenAI-ES).
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::NeuralNetwork;
+use crate::{scenario::Scenario, utils::gaussian, Config};
+
+pub const SIGMA_DEFAULT: f64 = 0.1;
+pub const LEARNING_RATE_DEFAULT: f64 = 0.05;
+pub const POPULATION_DEFAULT: usize = 50;
+/// evaluation of one candidate in the headless training ends after this many ticks
+pub const MAX_TICKS_DEFAULT: usize = 3000;
+const MOMENTUM: f64 = 0.9;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct EvolutionStrategy {
+    /// network with the current mean parameters
+    mean: NeuralNetwork,
+    /// standard deviation of the noise samples are created with
+    pub sigma: f64,
+    #[wasm_bindgen(js_name = learningRate)]
+    pub learning_rate: f64,
+    population: usize,
+    /// noise of the samples returned by the last `ask`, candidates are created in pairs `mean ± sigma * noise`
+    noise: Vec<Vec<f64>>,
+    velocity: Vec<f64>,
+    generation: usize,
+}
+
+#[wasm_bindgen]
+impl EvolutionStrategy {
+    /// # Arguments
+    /// * `start` - brain the search starts from, it also decides the shape of the brains
+    /// * `population` - number of brains sampled each generation, it is rounded up to even number
+    #[wasm_bindgen(constructor)]
+    pub fn new(start: &NeuralNetwork, sigma: f64, learning_rate: f64, population: usize) -> Self {
+        EvolutionStrategy {
+            velocity: vec![0.; start.parameters_count()],
+            mean: start.clone(),
+            sigma,
+            learning_rate,
+            population: population.max(2).div_ceil(2) * 2,
+            noise: vec![],
+            generation: 0,
+        }
+    }
+
+    /// Brain with the current mean parameters, ie the best guess of the strategy
+    pub fn mean(&self) -> NeuralNetwork {
+        self.mean.clone()
+    }
+
+    pub fn population(&self) -> usize {
+        self.population
+    }
+
+    /// Number of finished generations
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    /// Runs one generation, every candidate drives through the scenario without drawing anything
+    ///
+    /// # Returns
+    /// * `f64` - fitness of the mean brain after the update
+    #[wasm_bindgen(js_name = trainGeneration)]
+    pub fn train_generation(&mut self, config: &Config) -> f64 {
+        let scenario = Scenario::training();
+        let fitness = self
+            .ask()
+            .iter()
+            .map(|brain| scenario.evaluate(brain, config, MAX_TICKS_DEFAULT))
+            .collect::<Vec<f64>>();
+        self.tell(&fitness);
+
+        scenario.evaluate(&self.mean, config, MAX_TICKS_DEFAULT)
+    }
+}
+
+impl EvolutionStrategy {
+    /// Samples new population, fitness of the brains has to be reported back by [`EvolutionStrategy::tell`]
+    /// in the same order
+    pub fn ask(&mut self) -> Vec<NeuralNetwork> {
+        let parameters = self.mean.parameters().copied().collect::<Vec<f64>>();
+        self.noise = (0..self.population / 2)
+            .map(|_| parameters.iter().map(|_| gaussian()).collect())
+            .collect();
+
+        let mut brains = Vec::with_capacity(self.population);
+        for noise in self.noise.iter() {
+            // mirrored samples reduce variance of the gradient estimate
+            for sign in [1., -1.].iter() {
+                let values = parameters
+                    .iter()
+                    .zip(noise.iter())
+                    .map(|(p, n)| p + sign * self.sigma * n)
+                    .collect::<Vec<f64>>();
+                let mut brain = self.mean.clone();
+                brain.set_parameters(&values);
+                brains.push(brain);
+            }
+        }
+
+        brains
+    }
+
+    /// Moves the mean towards the better samples of the last [`EvolutionStrategy::ask`]
+    ///
+    /// Only the order of fitness matters, values are replaced by ranks, so a single lucky sample
+    /// can't throw the mean away.
+    pub fn tell(&mut self, fitness: &[f64]) {
+        assert_eq!(
+            fitness.len(),
+            self.noise.len() * 2,
+            "fitness has to be reported for every sample"
+        );
+
+        let ranks = centered_ranks(fitness);
+        let scale = 1. / (fitness.len() as f64 * self.sigma);
+
+        let mut gradient = vec![0.; self.velocity.len()];
+        for (i, noise) in self.noise.iter().enumerate() {
+            // difference of the mirrored pair
+            let weight = ranks[2 * i] - ranks[2 * i + 1];
+            for (g, n) in gradient.iter_mut().zip(noise.iter()) {
+                *g += weight * n * scale;
+            }
+        }
+
+        for (v, g) in self.velocity.iter_mut().zip(gradient.iter()) {
+            *v = MOMENTUM * *v + self.learning_rate * g;
+        }
+
+        let velocity = &self.velocity;
+        self.mean
+            .parameters_mut()
+            .zip(velocity.iter())
+            .for_each(|(p, v)| *p += v);
+
+        self.noise.clear();
+        self.generation += 1;
+    }
+}
+
+/// Replaces values by their rank scaled to `-0.5..=0.5`
+fn centered_ranks(values: &[f64]) -> Vec<f64> {
+    let mut order = (0..values.len()).collect::<Vec<usize>>();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+
+    let mut ranks = vec![0.; values.len()];
+    let max = (values.len().max(2) - 1) as f64;
+    for (rank, index) in order.into_iter().enumerate() {
+        ranks[index] = rank as f64 / max - 0.5;
+    }
+    ranks
+}
@@ -0,0 +1,261 @@
+//! Compact binary encoding of brains
+//!
+//! JSON of a brain repeats field names for every level and writes each weight as decimal text.
+//! The binary form stores only the topology, activations, weights and biases, so it is several
+//! times smaller. Saves store it as base64 string.
+//!
+//! Layout, all numbers are little endian:
+//! * header: magic `SDCB`, format version (`u8`), number of levels (`u32`)
+//! * each level: input count (`u32`), output count (`u32`), activation (`u8`), flags (`u8`,
+//!   bit 0 is set for recurrent levels), sigma (`f64`), weights, biases and recurrent weights (`f64` each)
+//!
+//! Weights are stored as `f64`, so encoding is lossless.
+
+use std::fmt;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use super::{Activation, Level, NeuralNetwork};
+
+const MAGIC: &[u8; 4] = b"SDCB";
+/// Version of the binary layout, bump it whenever the layout changes and keep decoding the old ones
+pub const FORMAT_VERSION: u8 = 1;
+
+const RECURRENT_FLAG: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// data does not start with the magic bytes, it is not a brain
+    NotBrain,
+    /// brain was encoded by newer version of the simulation
+    UnsupportedVersion(u8),
+    /// data ends before the whole brain was read
+    Truncated,
+    UnknownActivation(u8),
+    /// level's input count does not match output count of the previous level
+    Topology,
+    /// level has no inputs or outputs, or more weights than can be addressed
+    LevelSize,
+    Base64(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::NotBrain => write!(f, "data is not an encoded brain"),
+            DecodeError::UnsupportedVersion(version) => write!(
+                f,
+                "brain format version {version} is not supported, latest supported is {FORMAT_VERSION}"
+            ),
+            DecodeError::Truncated => write!(f, "encoded brain is truncated"),
+            DecodeError::UnknownActivation(activation) => {
+                write!(f, "unknown activation '{activation}'")
+            }
+            DecodeError::Topology => write!(f, "levels of the brain do not fit together"),
+            DecodeError::LevelSize => write!(f, "level of the brain has invalid size"),
+            DecodeError::Base64(e) => write!(f, "invalid base64: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Activation {
+    fn to_byte(self) -> u8 {
+        match self {
+            Activation::Step => 0,
+            Activation::Sigmoid => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, DecodeError> {
+        match byte {
+            0 => Ok(Activation::Step),
+            1 => Ok(Activation::Sigmoid),
+            _ => Err(DecodeError::UnknownActivation(byte)),
+        }
+    }
+}
+
+impl NeuralNetwork {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(9 + self.0.len() * 18 + self.parameters_count() * 8);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(FORMAT_VERSION);
+        bytes.extend_from_slice(&(self.0.len() as u32).to_le_bytes());
+
+        for level in self.0.iter() {
+            bytes.extend_from_slice(&(level.input_count() as u32).to_le_bytes());
+            bytes.extend_from_slice(&(level.output_count() as u32).to_le_bytes());
+            bytes.push(level.activation.to_byte());
+            bytes.push(match level.is_recurrent() {
+                true => RECURRENT_FLAG,
+                false => 0,
+            });
+            bytes.extend_from_slice(&level.sigma.to_le_bytes());
+            level
+                .weights
+                .iter()
+                .chain(level.biases.iter())
+                .chain(level.recurrent.iter())
+                .for_each(|value| bytes.extend_from_slice(&value.to_le_bytes()));
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(DecodeError::NotBrain);
+        }
+
+        match reader.u8()? {
+            FORMAT_VERSION => (),
+            version => return Err(DecodeError::UnsupportedVersion(version)),
+        }
+
+        let levels_count = reader.u32()? as usize;
+        let mut levels: Vec<Level> = Vec::with_capacity(levels_count.min(64));
+        for _ in 0..levels_count {
+            let input_count = reader.u32()? as usize;
+            let output_count = reader.u32()? as usize;
+            if input_count == 0 || output_count == 0 {
+                return Err(DecodeError::LevelSize);
+            }
+            if levels
+                .last()
+                .is_some_and(|previous| previous.output_count() != input_count)
+            {
+                return Err(DecodeError::Topology);
+            }
+
+            let activation = Activation::from_byte(reader.u8()?)?;
+            let recurrent = reader.u8()? & RECURRENT_FLAG != 0;
+            let sigma = reader.f64()?;
+            // counts come from the data, so they could overflow on 32 bit targets
+            let weights_count = input_count
+                .checked_mul(output_count)
+                .ok_or(DecodeError::LevelSize)?;
+            let weights = reader.f64s(weights_count)?;
+            let biases = reader.f64s(output_count)?;
+            let recurrent = match recurrent {
+                true => reader.f64s(
+                    output_count
+                        .checked_mul(output_count)
+                        .ok_or(DecodeError::LevelSize)?,
+                )?,
+                false => vec![],
+            };
+
+            levels.push(Level {
+                inputs: vec![0.; input_count],
+                outputs: vec![0.; output_count],
+                biases,
+                weights,
+                activation,
+                sigma,
+                memory: vec![0.; recurrent.len() / output_count],
+                recurrent,
+            });
+        }
+
+        if levels.is_empty() {
+            return Err(DecodeError::Topology);
+        }
+
+        Ok(NeuralNetwork(levels))
+    }
+
+    /// Binary form encoded as base64, see [`NeuralNetwork::to_bytes`]
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.to_bytes())
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, DecodeError> {
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| DecodeError::Base64(e.to_string()))?;
+        NeuralNetwork::from_bytes(&bytes)
+    }
+}
+
+/// Reads values from the front of the slice, running out of data is reported as [`DecodeError::Truncated`]
+pub(crate) struct Reader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, count: usize) -> Result<&'a [u8], DecodeError> {
+        if self.0.len() < count {
+            return Err(DecodeError::Truncated);
+        }
+
+        let (taken, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, DecodeError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn f32(&mut self) -> Result<f32, DecodeError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(f32::from_le_bytes(bytes))
+    }
+
+    fn f64(&mut self) -> Result<f64, DecodeError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(bytes))
+    }
+
+    fn f64s(&mut self, count: usize) -> Result<Vec<f64>, DecodeError> {
+        // checked before allocating, so corrupted count can't make us allocate gigabytes
+        if self.0.len() < count.saturating_mul(8) {
+            return Err(DecodeError::Truncated);
+        }
+
+        (0..count).map(|_| self.f64()).collect()
+    }
+}
+
+/// Serde adapter storing brain as the compact base64 string, use it with `#[serde(with = "compact")]`
+///
+/// Brains saved before the compact format existed are JSON objects, they are still accepted when loading.
+pub mod compact {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::NeuralNetwork;
+
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum StoredBrain {
+        Compact(String),
+        Json(NeuralNetwork),
+    }
+
+    pub fn serialize<S: Serializer>(
+        brain: &NeuralNetwork,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&brain.to_base64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<NeuralNetwork, D::Error> {
+        match StoredBrain::deserialize(deserializer)? {
+            StoredBrain::Compact(encoded) => {
+                NeuralNetwork::from_base64(&encoded).map_err(serde::de::Error::custom)
+            }
+            StoredBrain::Json(brain) => Ok(brain),
+        }
+    }
+}
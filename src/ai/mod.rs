@@ -1,10 +1,17 @@
 pub mod agents;
+pub mod dataset;
+pub mod es;
+pub mod format;
+pub mod mutation;
+pub mod neat;
+pub mod population;
+pub mod rl;
+pub mod training;
 
-use js_sys::Math::random;
-use std::cell::RefCell;
 use wasm_bindgen::prelude::wasm_bindgen;
 
-use crate::utils::lerp;
+use crate::{utils::random, Config};
+use mutation::Mutation;
 
 #[wasm_bindgen]
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -20,26 +27,113 @@ impl NeuralNetwork {
         Self(levels)
     }
 
-    pub fn feed_forward_2(&self, inputs: Vec<f64>) {
-        // SAFETY: each network is create with atleast input layer
-        let first_level = unsafe { self.0.get_unchecked(0) };
+    /// Runs the network, outputs of each level are written to its preallocated buffer,
+    /// so running the network does not allocate
+    ///
+    /// # Returns
+    /// * `&[f64]` - outputs of the last level
+    pub fn feed_forward(&mut self, inputs: &[f64]) -> &[f64] {
+        let (first, rest) = self
+            .0
+            .split_first_mut()
+            .expect("network has to have at least one level");
 
-        feed_forward(
-            &RefCell::new(inputs),
-            &first_level.outputs,
-            &first_level.weights,
-            &first_level.biases,
-        );
+        first.feed_forward(inputs);
+        let mut previous = first;
+        for level in rest.iter_mut() {
+            level.feed_forward(&previous.outputs);
+            previous = level;
+        }
+
+        &previous.outputs
+    }
 
-        for (index, level) in self.0.iter().skip(1).enumerate() {
-            feed_forward(
-                &self.0[index].outputs,
-                &level.outputs,
-                &level.weights,
-                &level.biases,
-            )
+    /// Creates network of the shape configured by `config`, with memory if [`Config::recurrent`] is set
+    pub fn from_config(config: &Config) -> Self {
+        let network = NeuralNetwork::new(&config.neurons_count());
+        match config.recurrent {
+            true => network.with_memory(),
+            false => network,
         }
     }
+
+    /// Makes every hidden level recurrent, see [`Level::recurrent`]
+    pub fn with_memory(mut self) -> Self {
+        let hidden = self.0.len().saturating_sub(1);
+        self.0
+            .iter_mut()
+            .take(hidden)
+            .for_each(|level| level.add_memory());
+        self
+    }
+
+    /// Network has at least one recurrent level
+    pub fn is_recurrent(&self) -> bool {
+        self.0.iter().any(Level::is_recurrent)
+    }
+
+    /// Forgets hidden state of recurrent levels, done whenever the network starts driving a new car
+    pub fn reset_memory(&mut self) {
+        self.0.iter_mut().for_each(Level::reset_memory);
+    }
+
+    /// Values of the network from the last time it was fed, first item are the inputs of the first level,
+    /// then outputs of each level, the last item are network's outputs
+    pub fn level_activations(&self) -> Vec<&[f64]> {
+        let inputs = self.0.first().map(|level| level.inputs.as_slice());
+        inputs
+            .into_iter()
+            .chain(self.0.iter().map(|level| level.outputs.as_slice()))
+            .collect()
+    }
+
+    /// Sets activation function of every level
+    pub fn with_activation(mut self, activation: Activation) -> Self {
+        self.0
+            .iter_mut()
+            .for_each(|level| level.activation = activation);
+        self
+    }
+
+    /// Number of all weights and biases in the network
+    pub fn parameters_count(&self) -> usize {
+        self.0.iter().map(Level::parameters_count).sum()
+    }
+
+    /// All weights and biases, level by level, weights of each level first (in the order they are stored,
+    /// see [`Level::weights`]) followed by its biases and recurrent weights
+    pub fn parameters(&self) -> impl Iterator<Item = &f64> {
+        self.0.iter().flat_map(|level| {
+            level
+                .weights
+                .iter()
+                .chain(level.biases.iter())
+                .chain(level.recurrent.iter())
+        })
+    }
+
+    /// Overwrites weights and biases, `values` are in the same order as [`NeuralNetwork::parameters`] returns them
+    pub fn set_parameters(&mut self, values: &[f64]) {
+        assert_eq!(
+            values.len(),
+            self.parameters_count(),
+            "number of values does not match number of parameters"
+        );
+        self.parameters_mut()
+            .zip(values.iter())
+            .for_each(|(parameter, value)| *parameter = *value);
+    }
+
+    /// Same as [`NeuralNetwork::parameters`] but mutable
+    pub fn parameters_mut(&mut self) -> impl Iterator<Item = &mut f64> {
+        self.0.iter_mut().flat_map(|level| {
+            level
+                .weights
+                .iter_mut()
+                .chain(level.biases.iter_mut())
+                .chain(level.recurrent.iter_mut())
+        })
+    }
 }
 
 #[wasm_bindgen]
@@ -52,57 +146,216 @@ impl NeuralNetwork {
         serde_json::from_str::<NeuralNetwork>(&json).ok()
     }
 
+    /// Brain in the compact base64 form, see [`format`]
+    pub fn serialize_brain_compact(&self) -> String {
+        self.to_base64()
+    }
+
+    pub fn deserialize_brain_compact(encoded: String) -> Option<NeuralNetwork> {
+        NeuralNetwork::from_base64(&encoded).ok()
+    }
+
+    /// Converts brain stored as JSON to the compact form
+    pub fn json_to_compact(json: String) -> Option<String> {
+        NeuralNetwork::deserialize_brain(json).map(|brain| brain.to_base64())
+    }
+
+    /// Converts brain stored in the compact form to JSON
+    pub fn compact_to_json(encoded: String) -> Option<String> {
+        NeuralNetwork::deserialize_brain_compact(encoded).map(|brain| brain.serialize_brain())
+    }
+
+    /// Creates network with sigmoid activations, which can be trained by [`training::Trainer`]
+    pub fn trainable(neuron_counts: &[usize]) -> Self {
+        NeuralNetwork::new(neuron_counts).with_activation(Activation::Sigmoid)
+    }
+
+    /// Runs the network and returns outputs of the last level, unlike [`NeuralNetwork::feed_forward`]
+    /// it does not need mutable access, but allocates and recurrent levels only read their memory, they do not update it
+    pub fn forward(&self, inputs: &[f64]) -> Vec<f64> {
+        self.0.iter().fold(inputs.to_vec(), |inputs, level| {
+            let mut outputs = vec![0.; level.output_count()];
+            level.compute(&inputs, &mut outputs);
+            outputs
+        })
+    }
+
     pub fn mutate(&self, mutation_rate: f64) -> Self {
-        let mut levels = self.0.clone();
+        self.mutate_with(&Mutation::lerp(mutation_rate))
+    }
+}
 
-        for level in levels.iter_mut() {
-            for bias in level.biases.iter_mut() {
-                *bias = lerp(*bias, random() * 2. - 1., mutation_rate);
-            }
+/// Function applied to the weighted sum of level's inputs
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Activation {
+    /// outputs 1 if the sum is positive, 0 otherwise
+    #[default]
+    Step,
+    /// smooth version of the step, it is differentiable so it can be trained with backpropagation
+    Sigmoid,
+}
 
-            for weight_vec in level.weights.iter_mut() {
-                for weight in weight_vec.iter_mut() {
-                    *weight = lerp(*weight, random() * 2. - 1., mutation_rate);
-                }
-            }
+impl Activation {
+    pub fn apply(&self, x: f64) -> f64 {
+        match self {
+            Activation::Step => ((x > 0.) as u8) as f64,
+            Activation::Sigmoid => 1. / (1. + (-x).exp()),
+        }
+    }
+
+    /// Derivative of the activation expressed using its output
+    ///
+    /// Step outputs are exactly 0 or 1 and its derivative is zero everywhere, so no gradient flows
+    /// through it and only networks with [`Activation::Sigmoid`] levels can be trained.
+    pub fn derivative(&self, output: f64) -> f64 {
+        match self {
+            Activation::Step => 0.,
+            Activation::Sigmoid => output * (1. - output),
         }
-        Self(levels)
     }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "LevelData", into = "LevelData")]
 pub struct Level {
+    /// values the level was last fed with
     pub inputs: Vec<f64>,
-    pub outputs: RefCell<Vec<f64>>,
+    /// values the level computed last time it was fed
+    pub outputs: Vec<f64>,
     pub biases: Vec<f64>,
-    pub weights: Vec<Vec<f64>>,
+    /// row-major matrix, weights of all inputs of the first output, then of the second output, etc.,
+    /// so computing one output reads contiguous memory
+    pub weights: Vec<f64>,
+    pub activation: Activation,
+    /// mutation strength of the level, evolves with self-adaptive mutation
+    pub sigma: f64,
+    /// weights from outputs of the previous tick to the outputs, row-major like [`Level::weights`],
+    /// empty if the level is not recurrent
+    ///
+    /// Recurrent level is Elman-style, its last outputs are its hidden state which is fed back
+    /// in the next tick, so the car can remember what its sensors saw before.
+    pub recurrent: Vec<f64>,
+    /// outputs of the previous tick, empty if the level is not recurrent
+    pub memory: Vec<f64>,
 }
 
 impl Level {
     pub fn new(input_count: usize, output_count: usize) -> Self {
         Level {
             inputs: vec![0.; input_count],
-            outputs: RefCell::new(vec![0.; output_count]),
+            outputs: vec![0.; output_count],
             biases: vec![],
             weights: vec![],
+            activation: Activation::default(),
+            sigma: mutation::SIGMA_DEFAULT,
+            recurrent: vec![],
+            memory: vec![],
         }
         .randomize()
     }
-}
 
-impl Level {
-    fn randomize(mut self) -> Self {
-        // for each input we need to generate a random weights
-        for _ in self.inputs.iter() {
-            let mut weights = vec![];
-            // we have one weight value for each output, from 1 input we map weights to each output
-            for _ in self.outputs.borrow().iter() {
-                weights.push(random() * 2.0 - 1.0);
+    /// Makes the level recurrent with random recurrent weights, does nothing if it already is
+    pub fn add_memory(&mut self) {
+        if self.is_recurrent() {
+            return;
+        }
+
+        let output_count = self.output_count();
+        self.recurrent = (0..output_count * output_count)
+            .map(|_| random() * 2.0 - 1.0)
+            .collect();
+        self.memory = vec![0.; output_count];
+    }
+
+    pub fn is_recurrent(&self) -> bool {
+        !self.recurrent.is_empty()
+    }
+
+    pub fn reset_memory(&mut self) {
+        self.memory.iter_mut().for_each(|value| *value = 0.);
+    }
+
+    /// Weight of the connection from `from` output of the previous tick to `to` output
+    pub fn recurrent_weight(&self, from: usize, to: usize) -> f64 {
+        self.recurrent[to * self.output_count() + from]
+    }
+
+    /// Number of weights and biases of the level, including recurrent weights
+    pub fn parameters_count(&self) -> usize {
+        self.weights.len() + self.biases.len() + self.recurrent.len()
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn output_count(&self) -> usize {
+        self.outputs.len()
+    }
+
+    /// Weight of the connection from `input` to `output`
+    pub fn weight(&self, input: usize, output: usize) -> f64 {
+        self.weights[output * self.input_count() + input]
+    }
+
+    /// Computes outputs of the level, they are kept in `outputs` and the inputs in `inputs`
+    pub fn feed_forward(&mut self, inputs: &[f64]) {
+        self.record_inputs(inputs);
+        let mut outputs = std::mem::take(&mut self.outputs);
+        self.compute(inputs, &mut outputs);
+        self.outputs = outputs;
+        self.remember();
+    }
+
+    fn compute(&self, inputs: &[f64], outputs: &mut [f64]) {
+        let input_count = self.input_count();
+        for (index, ((output, row), bias)) in outputs
+            .iter_mut()
+            .zip(self.weights.chunks_exact(input_count))
+            .zip(self.biases.iter())
+            .enumerate()
+        {
+            let mut sum = row
+                .iter()
+                .zip(inputs.iter())
+                .map(|(weight, input)| weight * input)
+                .sum::<f64>();
+
+            if self.is_recurrent() {
+                let output_count = self.memory.len();
+                sum += self.recurrent[index * output_count..(index + 1) * output_count]
+                    .iter()
+                    .zip(self.memory.iter())
+                    .map(|(weight, value)| weight * value)
+                    .sum::<f64>();
             }
-            self.weights.push(weights);
+
+            *output = self.activation.apply(sum + bias);
         }
+    }
+
+    fn record_inputs(&mut self, inputs: &[f64]) {
+        self.inputs
+            .iter_mut()
+            .zip(inputs.iter())
+            .for_each(|(recorded, input)| *recorded = *input);
+    }
 
-        for _ in 0..self.outputs.borrow().len() {
+    /// Keeps current outputs as the hidden state for the next tick
+    fn remember(&mut self) {
+        if self.is_recurrent() {
+            self.memory.copy_from_slice(&self.outputs);
+        }
+    }
+
+    fn randomize(mut self) -> Self {
+        // one weight for each pair of input and output
+        for _ in 0..self.input_count() * self.output_count() {
+            self.weights.push(random() * 2.0 - 1.0);
+        }
+
+        for _ in 0..self.output_count() {
             self.biases.push(random() * 2.0 - 1.0);
         }
 
@@ -110,19 +363,121 @@ impl Level {
     }
 }
 
-pub fn feed_forward(
-    inputs: &RefCell<Vec<f64>>,
-    outputs: &RefCell<Vec<f64>>,
-    weights: &[Vec<f64>],
-    biases: &[f64],
-) {
-    for (i, output) in outputs.borrow_mut().iter_mut().enumerate() {
-        let mut sum = 0.;
-        for (j, input) in inputs.borrow().iter().enumerate() {
-            sum += input * weights[j][i]
+/// Weights as they are stored in saves
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum Weights {
+    Flat(Vec<f64>),
+    /// saves created before weights were flattened, `weights[input][output]`
+    Nested(Vec<Vec<f64>>),
+}
+
+/// Serialized form of [`Level`], buffers of values are not stored, only their sizes
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+struct LevelData {
+    /// saves created before weights were flattened store the inputs instead
+    #[serde(default)]
+    input_count: usize,
+    #[serde(default, skip_serializing)]
+    inputs: Vec<f64>,
+    biases: Vec<f64>,
+    weights: Weights,
+    /// saves created before activations were configurable use step
+    #[serde(default)]
+    activation: Activation,
+    #[serde(default = "default_sigma")]
+    sigma: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    recurrent: Vec<f64>,
+    /// hidden state of recurrent level, kept so snapshots of the simulation resume exactly
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    memory: Vec<f64>,
+}
+
+fn default_sigma() -> f64 {
+    mutation::SIGMA_DEFAULT
+}
+
+impl std::convert::TryFrom<LevelData> for Level {
+    type Error = String;
+
+    /// Rejects levels whose buffers do not match their sizes, computing them would mix up the values or panic
+    fn try_from(data: LevelData) -> Result<Self, Self::Error> {
+        let input_count = match data.input_count {
+            0 => data.inputs.len(),
+            count => count,
+        };
+        let output_count = data.biases.len();
+        if input_count == 0 || output_count == 0 {
+            return Err(format!(
+                "level has {input_count} inputs and {output_count} outputs, it needs at least one of each"
+            ));
         }
 
-        // SAFETY: we can't get out of bounds as the index is based on iterator
-        *output = ((sum + unsafe { biases.get_unchecked(i) } > 0.) as u8) as f64;
+        let weights = match data.weights {
+            Weights::Flat(weights) => weights,
+            Weights::Nested(rows) => {
+                if rows.len() != input_count || rows.iter().any(|row| row.len() != output_count) {
+                    return Err(format!(
+                        "level with {input_count} inputs and {output_count} outputs has weights of different shape"
+                    ));
+                }
+                (0..output_count)
+                    .flat_map(|output| rows.iter().map(move |row| row[output]))
+                    .collect()
+            }
+        };
+        if weights.len() != input_count * output_count {
+            return Err(format!(
+                "level with {input_count} inputs and {output_count} outputs has {} weights",
+                weights.len()
+            ));
+        }
+
+        let recurrent_count = match data.recurrent.is_empty() {
+            true => 0,
+            false => output_count,
+        };
+        if data.recurrent.len() != recurrent_count * recurrent_count {
+            return Err(format!(
+                "level with {output_count} outputs has {} recurrent weights",
+                data.recurrent.len()
+            ));
+        }
+        let memory = match data.memory.len() {
+            0 => vec![0.; recurrent_count],
+            len if len == recurrent_count => data.memory,
+            len => {
+                return Err(format!(
+                    "level with {recurrent_count} remembered outputs has memory of {len} values"
+                ))
+            }
+        };
+
+        Ok(Level {
+            inputs: vec![0.; input_count],
+            outputs: vec![0.; output_count],
+            biases: data.biases,
+            weights,
+            activation: data.activation,
+            sigma: data.sigma,
+            memory,
+            recurrent: data.recurrent,
+        })
+    }
+}
+
+impl From<Level> for LevelData {
+    fn from(level: Level) -> Self {
+        LevelData {
+            input_count: level.input_count(),
+            inputs: vec![],
+            biases: level.biases,
+            weights: Weights::Flat(level.weights),
+            activation: level.activation,
+            sigma: level.sigma,
+            recurrent: level.recurrent,
+            memory: level.memory,
+        }
     }
 }
@@ -0,0 +1,160 @@
+//! Mutation operators used when breeding new generations of brains
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::NeuralNetwork;
+use crate::{
+    utils::{gaussian, lerp, random},
+    Config,
+};
+
+/// Initial mutation strength of each level for self-adaptive mutation
+pub const SIGMA_DEFAULT: f64 = 0.1;
+/// Lower bound of self-adapted sigma, so it can't shrink to nothing
+const SIGMA_MIN: f64 = 0.001;
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum MutationOperator {
+    /// moves value towards uniform random value by `rate`
+    #[default]
+    Lerp,
+    /// adds normally distributed noise with standard deviation `sigma`
+    Gaussian,
+    /// replaces value by uniform random value
+    Reset,
+    /// like gaussian, but every level carries its own sigma which is mutated too, so mutation strength evolves
+    SelfAdaptive,
+}
+
+/// How brains are mutated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mutation {
+    pub operator: MutationOperator,
+    pub rate: f64,
+    /// probability that a weight or bias is mutated at all
+    pub probability: f64,
+    pub sigma: f64,
+}
+
+impl Mutation {
+    /// Mutation every brain used before operators were configurable
+    pub fn lerp(rate: f64) -> Self {
+        Mutation {
+            operator: MutationOperator::Lerp,
+            rate,
+            probability: 1.,
+            sigma: SIGMA_DEFAULT,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Mutation {
+            operator: config.mutation_operator,
+            rate: config.mutation_rate,
+            probability: config.mutation_probability,
+            sigma: config.mutation_sigma,
+        }
+    }
+
+    /// Same mutation with strength multiplied by `factor`, see [`MutationSchedule`]
+    pub fn scaled(self, factor: f64) -> Self {
+        Mutation {
+            rate: self.rate * factor,
+            sigma: self.sigma * factor,
+            ..self
+        }
+    }
+
+    fn apply(&self, value: f64, sigma: f64) -> f64 {
+        if self.probability < 1. && random() >= self.probability {
+            return value;
+        }
+
+        match self.operator {
+            MutationOperator::Lerp => lerp(value, random() * 2. - 1., self.rate),
+            MutationOperator::Gaussian => value + gaussian() * self.sigma,
+            MutationOperator::Reset => random() * 2. - 1.,
+            MutationOperator::SelfAdaptive => value + gaussian() * sigma,
+        }
+    }
+}
+
+impl NeuralNetwork {
+    /// Returns mutated copy of the network
+    pub fn mutate_with(&self, mutation: &Mutation) -> Self {
+        let mut network = self.clone();
+
+        for level in network.0.iter_mut() {
+            if let MutationOperator::SelfAdaptive = mutation.operator {
+                // learning rate of the log-normal self-adaptation as suggested for evolution strategies
+                let tau = 1. / (level.parameters_count() as f64).sqrt();
+                level.sigma = (level.sigma * (tau * gaussian()).exp()).max(SIGMA_MIN);
+            }
+
+            let sigma = level.sigma;
+            for bias in level.biases.iter_mut() {
+                *bias = mutation.apply(*bias, sigma);
+            }
+
+            for weight in level.weights.iter_mut().chain(level.recurrent.iter_mut()) {
+                *weight = mutation.apply(*weight, sigma);
+            }
+        }
+
+        network
+    }
+}
+
+/// Lowers mutation strength when the best fitness stops improving, so the population can fine tune
+/// the solution it converged to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MutationSchedule {
+    /// number of generations without improvement after which the strength is lowered, 0 turns schedule off
+    pub patience: usize,
+    /// strength is multiplied by this every time it is lowered
+    pub factor: f64,
+    /// strength is never lowered below this
+    pub min_scale: f64,
+    scale: f64,
+    best: f64,
+    stale: usize,
+}
+
+impl MutationSchedule {
+    pub fn new(patience: usize, factor: f64) -> Self {
+        MutationSchedule {
+            patience,
+            factor,
+            min_scale: 0.05,
+            scale: 1.,
+            best: f64::NEG_INFINITY,
+            stale: 0,
+        }
+    }
+
+    /// Multiplier of the configured mutation strength
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Records best fitness of the finished generation
+    ///
+    /// # Returns
+    /// * `f64` - multiplier of mutation strength for the next generation
+    pub fn update(&mut self, best_fitness: f64) -> f64 {
+        if best_fitness > self.best {
+            self.best = best_fitness;
+            self.stale = 0;
+        } else {
+            self.stale += 1;
+        }
+
+        if self.patience > 0 && self.stale >= self.patience {
+            self.scale = (self.scale * self.factor).max(self.min_scale);
+            self.stale = 0;
+        }
+
+        self.scale
+    }
+}
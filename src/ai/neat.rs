@@ -0,0 +1,675 @@
+//! NeuroEvolution of Augmenting Topologies
+//!
+//! Instead of fixed stack of fully connected [`super::Level`]s, genome is a list of nodes and connections
+//! between them. Evolution starts with inputs connected directly to outputs and adds new nodes and
+//! connections over time. Every structural change gets an innovation number, so genes of two genomes
+//! can be aligned when crossing them over or when measuring how different they are.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::Activation;
+use crate::utils::{lerp, random};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum NodeKind {
+    Input,
+    Hidden,
+    Output,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct NodeGene {
+    pub id: usize,
+    pub kind: NodeKind,
+    pub bias: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ConnectionGene {
+    pub innovation: usize,
+    pub from: usize,
+    pub to: usize,
+    pub weight: f64,
+    pub enabled: bool,
+}
+
+/// Hands out innovation numbers and node ids, so the same structural mutation gets the same number
+/// no matter in which genome it happened
+#[derive(Debug, Clone, Default)]
+pub struct InnovationTracker {
+    next_innovation: usize,
+    next_node_id: usize,
+    /// (from, to) => innovation number
+    connections: HashMap<(usize, usize), usize>,
+    /// innovation of split connection => id of the node created by the split
+    splits: HashMap<usize, usize>,
+}
+
+impl InnovationTracker {
+    pub fn new(inputs: usize, outputs: usize) -> Self {
+        InnovationTracker {
+            next_node_id: inputs + outputs,
+            ..InnovationTracker::default()
+        }
+    }
+
+    fn connection(&mut self, from: usize, to: usize) -> usize {
+        let next_innovation = &mut self.next_innovation;
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            *next_innovation += 1;
+            *next_innovation - 1
+        })
+    }
+
+    fn split(&mut self, innovation: usize) -> usize {
+        let next_node_id = &mut self.next_node_id;
+        *self.splits.entry(innovation).or_insert_with(|| {
+            *next_node_id += 1;
+            *next_node_id - 1
+        })
+    }
+
+    fn fresh_node(&mut self) -> usize {
+        self.next_node_id += 1;
+        self.next_node_id - 1
+    }
+}
+
+/// Tunables of the NEAT evolution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NeatSettings {
+    /// how much are weights and biases moved towards random value, same as `Config::mutation_rate`
+    pub mutation_rate: f64,
+    pub add_connection_probability: f64,
+    pub add_node_probability: f64,
+    /// weight of excess genes in compatibility distance
+    pub excess_coefficient: f64,
+    /// weight of disjoint genes in compatibility distance
+    pub disjoint_coefficient: f64,
+    /// weight of average weight difference of matching genes in compatibility distance
+    pub weight_coefficient: f64,
+    /// genomes closer than this belong to the same species
+    pub compatibility_threshold: f64,
+}
+
+impl Default for NeatSettings {
+    fn default() -> Self {
+        NeatSettings {
+            mutation_rate: 0.2,
+            add_connection_probability: 0.1,
+            add_node_probability: 0.05,
+            excess_coefficient: 1.,
+            disjoint_coefficient: 1.,
+            weight_coefficient: 0.4,
+            compatibility_threshold: 3.,
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Genome {
+    #[wasm_bindgen(skip)]
+    pub nodes: Vec<NodeGene>,
+    #[wasm_bindgen(skip)]
+    pub connections: Vec<ConnectionGene>,
+    /// values of nodes from the last activation, used for visualization
+    #[wasm_bindgen(skip)]
+    #[serde(skip)]
+    pub values: RefCell<HashMap<usize, f64>>,
+}
+
+#[wasm_bindgen]
+impl Genome {
+    pub fn serialize_genome(&self) -> String {
+        serde_json::to_string(&self).expect("failed to serialize genome")
+    }
+
+    pub fn deserialize_genome(json: String) -> Option<Genome> {
+        serde_json::from_str::<Genome>(&json).ok()
+    }
+}
+
+impl Genome {
+    /// Minimal genome, every input is connected to every output with random weight
+    pub fn new(inputs: usize, outputs: usize, tracker: &mut InnovationTracker) -> Self {
+        let nodes = (0..inputs)
+            .map(|id| NodeGene {
+                id,
+                kind: NodeKind::Input,
+                bias: 0.,
+            })
+            .chain((inputs..inputs + outputs).map(|id| NodeGene {
+                id,
+                kind: NodeKind::Output,
+                bias: random() * 2. - 1.,
+            }))
+            .collect();
+
+        let mut connections = vec![];
+        for from in 0..inputs {
+            for to in inputs..inputs + outputs {
+                connections.push(ConnectionGene {
+                    innovation: tracker.connection(from, to),
+                    from,
+                    to,
+                    weight: random() * 2. - 1.,
+                    enabled: true,
+                });
+            }
+        }
+
+        Genome {
+            nodes,
+            connections,
+            values: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn node(&self, id: usize) -> Option<&NodeGene> {
+        self.nodes.iter().find(|n| n.id == id)
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = &NodeGene> {
+        self.nodes.iter().filter(|n| n.kind == NodeKind::Input)
+    }
+
+    pub fn outputs(&self) -> impl Iterator<Item = &NodeGene> {
+        self.nodes.iter().filter(|n| n.kind == NodeKind::Output)
+    }
+
+    /// Runs the inputs through the genome and returns values of output nodes
+    pub fn activate(&self, inputs: &[f64]) -> Vec<f64> {
+        let mut values = self.values.borrow_mut();
+        values.clear();
+
+        for (node, input) in self.inputs().zip(inputs.iter()) {
+            values.insert(node.id, *input);
+        }
+
+        for id in self.topological_order() {
+            let Some(node) = self.node(id).filter(|n| n.kind != NodeKind::Input) else {
+                continue;
+            };
+
+            let sum = self
+                .connections
+                .iter()
+                .filter(|c| c.enabled && c.to == id)
+                .map(|c| values.get(&c.from).copied().unwrap_or_default() * c.weight)
+                .sum::<f64>();
+
+            values.insert(id, Activation::Sigmoid.apply(sum + node.bias));
+        }
+
+        self.outputs()
+            .map(|n| values.get(&n.id).copied().unwrap_or_default())
+            .collect()
+    }
+
+    /// Node ids ordered so every node comes after all nodes it takes input from
+    pub fn topological_order(&self) -> Vec<usize> {
+        let mut incoming = self
+            .nodes
+            .iter()
+            .map(|n| (n.id, 0))
+            .collect::<HashMap<usize, usize>>();
+        for connection in self.connections.iter().filter(|c| c.enabled) {
+            *incoming.entry(connection.to).or_insert(0) += 1;
+        }
+
+        let mut ready = self
+            .nodes
+            .iter()
+            .filter(|n| incoming[&n.id] == 0)
+            .map(|n| n.id)
+            .collect::<Vec<usize>>();
+        let mut order = Vec::with_capacity(self.nodes.len());
+
+        while let Some(id) = ready.pop() {
+            order.push(id);
+            for connection in self
+                .connections
+                .iter()
+                .filter(|c| c.enabled && c.from == id)
+            {
+                let Some(count) = incoming.get_mut(&connection.to) else {
+                    continue;
+                };
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(connection.to);
+                }
+            }
+        }
+
+        order
+    }
+
+    /// How many connections lie on the longest path from any input to the node, used to lay nodes out in levels
+    pub fn depths(&self) -> HashMap<usize, usize> {
+        let mut depths = HashMap::new();
+        for id in self.topological_order() {
+            let depth = self
+                .connections
+                .iter()
+                .filter(|c| c.enabled && c.to == id)
+                .map(|c| depths.get(&c.from).copied().unwrap_or_default() + 1)
+                .max()
+                .unwrap_or_default();
+            depths.insert(id, depth);
+        }
+        depths
+    }
+
+    /// IDs of the nodes split into levels they are drawn in, inputs are in the first level, outputs in the last one
+    /// and hidden nodes in between by their depth
+    pub fn levels(&self) -> Vec<Vec<usize>> {
+        let depths = self.depths();
+        // hidden node which lost all its incoming connections has depth 0, but it still belongs between inputs and outputs
+        let hidden_depth =
+            |node: &NodeGene| depths.get(&node.id).copied().unwrap_or_default().max(1);
+        let output_depth = self
+            .nodes
+            .iter()
+            .filter(|n| n.kind == NodeKind::Hidden)
+            .map(hidden_depth)
+            .max()
+            .unwrap_or_default()
+            + 1;
+
+        let mut levels = vec![vec![]; output_depth + 1];
+        for node in self.nodes.iter() {
+            let depth = match node.kind {
+                NodeKind::Input => 0,
+                NodeKind::Output => output_depth,
+                NodeKind::Hidden => hidden_depth(node),
+            };
+            levels[depth].push(node.id);
+        }
+        levels
+    }
+
+    /// Would connection `from` -> `to` create a loop
+    fn creates_cycle(&self, from: usize, to: usize) -> bool {
+        // we look for path from `to` back to `from`, disabled connections count as they can be enabled by crossover
+        let mut stack = vec![to];
+        let mut visited = vec![];
+        while let Some(id) = stack.pop() {
+            if id == from {
+                return true;
+            }
+            if visited.contains(&id) {
+                continue;
+            }
+            visited.push(id);
+            stack.extend(
+                self.connections
+                    .iter()
+                    .filter(|c| c.from == id)
+                    .map(|c| c.to),
+            );
+        }
+        false
+    }
+
+    /// Returns mutated copy of the genome
+    pub fn mutate(&self, settings: &NeatSettings, tracker: &mut InnovationTracker) -> Self {
+        let mut genome = self.clone();
+
+        for connection in genome.connections.iter_mut() {
+            connection.weight = lerp(
+                connection.weight,
+                random() * 2. - 1.,
+                settings.mutation_rate,
+            );
+        }
+
+        for node in genome
+            .nodes
+            .iter_mut()
+            .filter(|n| n.kind != NodeKind::Input)
+        {
+            node.bias = lerp(node.bias, random() * 2. - 1., settings.mutation_rate);
+        }
+
+        if random() < settings.add_connection_probability {
+            genome.add_connection(tracker);
+        }
+
+        if random() < settings.add_node_probability {
+            genome.add_node(tracker);
+        }
+
+        genome
+    }
+
+    /// Connects two not yet connected nodes, does nothing if it can't find any after few attempts
+    pub fn add_connection(&mut self, tracker: &mut InnovationTracker) {
+        const ATTEMPTS: usize = 20;
+
+        let sources = self
+            .nodes
+            .iter()
+            .filter(|n| n.kind != NodeKind::Output)
+            .map(|n| n.id)
+            .collect::<Vec<usize>>();
+        let targets = self
+            .nodes
+            .iter()
+            .filter(|n| n.kind != NodeKind::Input)
+            .map(|n| n.id)
+            .collect::<Vec<usize>>();
+
+        for _ in 0..ATTEMPTS {
+            let from = sources[(random() * sources.len() as f64) as usize % sources.len()];
+            let to = targets[(random() * targets.len() as f64) as usize % targets.len()];
+
+            let exists = self
+                .connections
+                .iter()
+                .any(|c| c.from == from && c.to == to);
+            if from == to || exists || self.creates_cycle(from, to) {
+                continue;
+            }
+
+            self.connections.push(ConnectionGene {
+                innovation: tracker.connection(from, to),
+                from,
+                to,
+                weight: random() * 2. - 1.,
+                enabled: true,
+            });
+            return;
+        }
+    }
+
+    /// Splits random enabled connection by a new node, connection into the node gets weight 1
+    /// and connection out of it the old weight, so the behaviour changes as little as possible
+    pub fn add_node(&mut self, tracker: &mut InnovationTracker) {
+        let enabled = self
+            .connections
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.enabled)
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+        if enabled.is_empty() {
+            return;
+        }
+
+        let index = enabled[(random() * enabled.len() as f64) as usize % enabled.len()];
+        self.connections[index].enabled = false;
+        let split = self.connections[index].clone();
+
+        let mut id = tracker.split(split.innovation);
+        // the same connection was already split in this genome and then re-enabled by crossover
+        if self.node(id).is_some() {
+            id = tracker.fresh_node();
+        }
+
+        self.nodes.push(NodeGene {
+            id,
+            kind: NodeKind::Hidden,
+            bias: 0.,
+        });
+        self.connections.push(ConnectionGene {
+            innovation: tracker.connection(split.from, id),
+            from: split.from,
+            to: id,
+            weight: 1.,
+            enabled: true,
+        });
+        self.connections.push(ConnectionGene {
+            innovation: tracker.connection(id, split.to),
+            from: id,
+            to: split.to,
+            weight: split.weight,
+            enabled: true,
+        });
+    }
+
+    /// Creates child of two genomes, matching genes are picked randomly from either parent,
+    /// disjoint and excess genes are taken from the fitter parent only
+    ///
+    /// # Arguments
+    /// * `fitter` - parent with better fitness, child gets its structure
+    /// * `other` - the other parent
+    pub fn crossover(fitter: &Genome, other: &Genome) -> Genome {
+        let other_connections = other
+            .connections
+            .iter()
+            .map(|c| (c.innovation, c))
+            .collect::<HashMap<usize, &ConnectionGene>>();
+
+        let connections = fitter
+            .connections
+            .iter()
+            .map(|gene| match other_connections.get(&gene.innovation) {
+                Some(other_gene) => {
+                    let mut child_gene = match random() < 0.5 {
+                        true => gene.clone(),
+                        false => (*other_gene).clone(),
+                    };
+                    // gene disabled in either parent has a chance to stay disabled
+                    child_gene.enabled = (gene.enabled && other_gene.enabled) || random() > 0.75;
+                    child_gene
+                }
+                None => gene.clone(),
+            })
+            .collect();
+
+        let nodes = fitter
+            .nodes
+            .iter()
+            .map(|node| match other.node(node.id) {
+                Some(other_node) if random() < 0.5 => other_node.clone(),
+                _ => node.clone(),
+            })
+            .collect();
+
+        Genome {
+            nodes,
+            connections,
+            values: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// How different two genomes are, based on number of not matching genes and weight differences of matching ones
+    pub fn compatibility_distance(&self, other: &Genome, settings: &NeatSettings) -> f64 {
+        let own = self
+            .connections
+            .iter()
+            .map(|c| (c.innovation, c.weight))
+            .collect::<HashMap<usize, f64>>();
+        let others = other
+            .connections
+            .iter()
+            .map(|c| (c.innovation, c.weight))
+            .collect::<HashMap<usize, f64>>();
+
+        let own_max = own.keys().max().copied().unwrap_or_default();
+        let others_max = others.keys().max().copied().unwrap_or_default();
+
+        let mut excess = 0.;
+        let mut disjoint = 0.;
+        let mut weight_difference = 0.;
+        let mut matching = 0.;
+
+        for (innovation, weight) in own.iter() {
+            match others.get(innovation) {
+                Some(other_weight) => {
+                    weight_difference += (weight - other_weight).abs();
+                    matching += 1.;
+                }
+                None if *innovation > others_max => excess += 1.,
+                None => disjoint += 1.,
+            }
+        }
+
+        for innovation in others.keys().filter(|i| !own.contains_key(i)) {
+            match *innovation > own_max {
+                true => excess += 1.,
+                false => disjoint += 1.,
+            }
+        }
+
+        // small genomes are not normalized, as suggested by the original paper
+        let genes = own.len().max(others.len());
+        let normalization = if genes < 20 { 1. } else { genes as f64 };
+        let average_weight_difference = match matching > 0. {
+            true => weight_difference / matching,
+            false => 0.,
+        };
+
+        settings.excess_coefficient * excess / normalization
+            + settings.disjoint_coefficient * disjoint / normalization
+            + settings.weight_coefficient * average_weight_difference
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Species {
+    /// genome new members are compared with
+    pub representative: Genome,
+    /// indexes of genomes of the population
+    pub members: Vec<usize>,
+}
+
+/// Population of genomes evolved by NEAT
+#[derive(Debug, Clone)]
+pub struct Neat {
+    pub genomes: Vec<Genome>,
+    pub species: Vec<Species>,
+    pub settings: NeatSettings,
+    pub generation: usize,
+    tracker: InnovationTracker,
+}
+
+impl Neat {
+    pub fn new(inputs: usize, outputs: usize, population: usize, settings: NeatSettings) -> Self {
+        let mut tracker = InnovationTracker::new(inputs, outputs);
+        let genomes = (0..population)
+            .map(|_| Genome::new(inputs, outputs, &mut tracker))
+            .collect();
+
+        let mut neat = Neat {
+            genomes,
+            species: vec![],
+            settings,
+            generation: 0,
+            tracker,
+        };
+        neat.speciate();
+        neat
+    }
+
+    /// Sorts genomes into species, each genome joins the first species whose representative is close enough
+    pub fn speciate(&mut self) {
+        let mut species = self
+            .species
+            .drain(..)
+            .map(|s| Species {
+                representative: s.representative,
+                members: vec![],
+            })
+            .collect::<Vec<Species>>();
+
+        for (index, genome) in self.genomes.iter().enumerate() {
+            match species.iter_mut().find(|s| {
+                s.representative
+                    .compatibility_distance(genome, &self.settings)
+                    < self.settings.compatibility_threshold
+            }) {
+                Some(s) => s.members.push(index),
+                None => species.push(Species {
+                    representative: genome.clone(),
+                    members: vec![index],
+                }),
+            }
+        }
+
+        species.retain(|s| !s.members.is_empty());
+        // new representative is random member of the current generation
+        for s in species.iter_mut() {
+            let member = s.members[(random() * s.members.len() as f64) as usize % s.members.len()];
+            s.representative = self.genomes[member].clone();
+        }
+
+        self.species = species;
+    }
+
+    /// Breeds next generation
+    ///
+    /// Species get number of offspring proportional to their shared fitness, so one big species
+    /// can't take over the whole population. Best genome of every species with more than one member
+    /// is copied unchanged, the rest are children of the better half of the species.
+    ///
+    /// # Arguments
+    /// * `fitness` - fitness of each genome, in the same order as `genomes`
+    pub fn evolve(&mut self, fitness: &[f64]) {
+        let population = self.genomes.len();
+        // shared fitness needs positive values
+        let min = fitness.iter().copied().fold(f64::INFINITY, f64::min);
+        let shifted = fitness.iter().map(|f| f - min + 1.).collect::<Vec<f64>>();
+
+        let shared = self
+            .species
+            .iter()
+            .map(|s| s.members.iter().map(|m| shifted[*m]).sum::<f64>() / s.members.len() as f64)
+            .collect::<Vec<f64>>();
+        let total = shared.iter().sum::<f64>();
+
+        let mut offspring = shared
+            .iter()
+            .map(|s| (s / total * population as f64).floor() as usize)
+            .collect::<Vec<usize>>();
+        // rounding down leaves some places free, they go to the best species
+        let best_species = shared
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, _)| i)
+            .unwrap_or_default();
+        let assigned = offspring.iter().sum::<usize>();
+        if let Some(o) = offspring.get_mut(best_species) {
+            *o += population.saturating_sub(assigned);
+        }
+
+        let mut next = Vec::with_capacity(population);
+        for (species, count) in self.species.iter().zip(offspring) {
+            if count == 0 {
+                continue;
+            }
+
+            let mut members = species.members.clone();
+            members.sort_by(|a, b| fitness[*b].total_cmp(&fitness[*a]));
+
+            let mut count = count;
+            if members.len() > 1 {
+                next.push(self.genomes[members[0]].clone());
+                count -= 1;
+            }
+
+            let parents = &members[..members.len().div_ceil(2)];
+            for _ in 0..count {
+                let first = parents[(random() * parents.len() as f64) as usize % parents.len()];
+                let second = parents[(random() * parents.len() as f64) as usize % parents.len()];
+                let (fitter, other) = match fitness[first] >= fitness[second] {
+                    true => (first, second),
+                    false => (second, first),
+                };
+
+                let child = Genome::crossover(&self.genomes[fitter], &self.genomes[other]);
+                next.push(child.mutate(&self.settings, &mut self.tracker));
+            }
+        }
+
+        next.truncate(population);
+        self.genomes = next;
+        self.generation += 1;
+        self.speciate();
+    }
+}
@@ -0,0 +1,413 @@
+//! Tools keeping the population of brains diverse
+//!
+//! Without them every generation is bred from the single best brain and the population quickly
+//! collapses onto one solution. Brains are clustered into species by distance of their weights,
+//! fitness is shared within species, so a big species can't take over, and optionally agents are
+//! rewarded for reaching states no agent reached before.
+
+use std::collections::{HashMap, HashSet};
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::{mutation::Mutation, NeuralNetwork};
+use crate::{utils::random, Config};
+
+type AgentId = usize;
+
+/// Summary of one finished generation
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerationStats {
+    pub generation: usize,
+    #[wasm_bindgen(js_name = bestFitness)]
+    pub best_fitness: f64,
+    #[wasm_bindgen(js_name = meanFitness)]
+    pub mean_fitness: f64,
+    /// mean weight distance between every pair of brains
+    pub diversity: f64,
+    pub species: usize,
+    /// states first reached in this generation, 0 if novelty search is off
+    #[wasm_bindgen(js_name = novelStates)]
+    pub novel_states: usize,
+    /// multiplier of mutation strength used to breed the next generation
+    #[wasm_bindgen(js_name = mutationScale)]
+    pub mutation_scale: f64,
+    #[wasm_bindgen(js_name = medianFitness)]
+    #[serde(default)]
+    pub median_fitness: f64,
+    #[wasm_bindgen(js_name = worstFitness)]
+    #[serde(default)]
+    pub worst_fitness: f64,
+    /// agents which did not crash until the generation ended
+    #[serde(default)]
+    pub survivors: usize,
+    /// milliseconds from the start to the end of the generation
+    #[wasm_bindgen(js_name = wallTime)]
+    #[serde(default)]
+    pub wall_time: f64,
+}
+
+impl GenerationStats {
+    /// Columns of [`GenerationStats::to_csv`]
+    pub const CSV_HEADER: &'static str =
+        "generation,best_fitness,mean_fitness,median_fitness,worst_fitness,\
+        survivors,diversity,species,novel_states,mutation_scale,wall_time";
+
+    /// Stats with fitness summary of the generation, other fields are left default
+    pub fn from_fitness(generation: usize, fitness: &[f64]) -> Self {
+        let mut sorted = fitness.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let median_fitness = match sorted.len() {
+            0 => 0.,
+            len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.,
+            len => sorted[len / 2],
+        };
+
+        GenerationStats {
+            generation,
+            best_fitness: sorted.last().copied().unwrap_or_default(),
+            mean_fitness: sorted.iter().sum::<f64>() / sorted.len().max(1) as f64,
+            median_fitness,
+            worst_fitness: sorted.first().copied().unwrap_or_default(),
+            ..GenerationStats::default()
+        }
+    }
+
+    /// History of generations as CSV with [`GenerationStats::CSV_HEADER`], one row per generation
+    pub fn to_csv(history: &[GenerationStats]) -> String {
+        let mut csv = String::from(Self::CSV_HEADER);
+        csv.push('\n');
+
+        for stats in history {
+            let row = [
+                stats.generation.to_string(),
+                stats.best_fitness.to_string(),
+                stats.mean_fitness.to_string(),
+                stats.median_fitness.to_string(),
+                stats.worst_fitness.to_string(),
+                stats.survivors.to_string(),
+                stats.diversity.to_string(),
+                stats.species.to_string(),
+                stats.novel_states.to_string(),
+                stats.mutation_scale.to_string(),
+                stats.wall_time.to_string(),
+            ]
+            .join(",");
+            csv.push_str(&row);
+            csv.push('\n');
+        }
+
+        csv
+    }
+}
+
+/// Root mean square difference of parameters of two brains, brains of different shapes are infinitely far apart
+pub fn weight_distance(a: &NeuralNetwork, b: &NeuralNetwork) -> f64 {
+    if a.parameters_count() != b.parameters_count() {
+        return f64::INFINITY;
+    }
+
+    let sum = a
+        .parameters()
+        .zip(b.parameters())
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>();
+
+    (sum / a.parameters_count().max(1) as f64).sqrt()
+}
+
+/// Mean weight distance between every pair of brains
+pub fn diversity(brains: &[&NeuralNetwork]) -> f64 {
+    let mut sum = 0.;
+    let mut pairs = 0;
+    for (i, a) in brains.iter().enumerate() {
+        for b in brains.iter().skip(i + 1) {
+            sum += weight_distance(a, b);
+            pairs += 1;
+        }
+    }
+
+    match pairs {
+        0 => 0.,
+        _ => sum / pairs as f64,
+    }
+}
+
+/// Splits brains into species, brain joins the first species whose first member is closer than `threshold`
+///
+/// # Returns
+/// * `Vec<Vec<usize>>` - indexes of brains of each species
+pub fn cluster(brains: &[&NeuralNetwork], threshold: f64) -> Vec<Vec<usize>> {
+    let mut species: Vec<Vec<usize>> = vec![];
+    for (index, brain) in brains.iter().enumerate() {
+        match species
+            .iter_mut()
+            .find(|s| weight_distance(brains[s[0]], brain) < threshold)
+        {
+            Some(s) => s.push(index),
+            None => species.push(vec![index]),
+        }
+    }
+    species
+}
+
+/// Divides fitness of each brain by the size of its species
+///
+/// Fitness is shifted to be positive first, otherwise dividing would favour big species with negative fitness.
+pub fn shared_fitness(fitness: &[f64], species: &[Vec<usize>]) -> Vec<f64> {
+    let min = fitness.iter().copied().fold(f64::INFINITY, f64::min);
+    let mut shared = fitness.iter().map(|f| f - min + 1.).collect::<Vec<f64>>();
+
+    for members in species.iter() {
+        for member in members.iter() {
+            shared[*member] /= members.len() as f64;
+        }
+    }
+
+    shared
+}
+
+/// Remembers which (lane, distance) states agents reached, agents get novelty score for every state
+/// not reached by any agent of the previous generations
+#[derive(Debug, Clone)]
+pub struct NoveltyArchive {
+    /// length of the road that counts as one state
+    bucket: f64,
+    visited: HashSet<(i32, i64)>,
+    /// states reached in the current generation, they become part of `visited` once the generation ends
+    current: HashSet<(i32, i64)>,
+    last_state: HashMap<AgentId, (i32, i64)>,
+    scores: HashMap<AgentId, f64>,
+}
+
+impl NoveltyArchive {
+    pub fn new(bucket: f64) -> Self {
+        NoveltyArchive {
+            bucket,
+            visited: HashSet::new(),
+            current: HashSet::new(),
+            last_state: HashMap::new(),
+            scores: HashMap::new(),
+        }
+    }
+
+    /// Records that agent reached `lane` at `distance`
+    pub fn visit(&mut self, id: AgentId, lane: i32, distance: f64) {
+        let state = (lane, (distance / self.bucket).floor() as i64);
+        if self.last_state.insert(id, state) == Some(state) {
+            return;
+        }
+
+        self.current.insert(state);
+        if !self.visited.contains(&state) {
+            *self.scores.entry(id).or_insert(0.) += 1.;
+        }
+    }
+
+    /// Number of novel states the agent reached in the current generation
+    pub fn score(&self, id: AgentId) -> f64 {
+        self.scores.get(&id).copied().unwrap_or_default()
+    }
+
+    /// Moves states of the current generation into the archive and resets scores
+    ///
+    /// # Returns
+    /// * `usize` - number of states first reached in the generation
+    pub fn end_generation(&mut self) -> usize {
+        let before = self.visited.len();
+        self.visited.extend(self.current.drain());
+        self.last_state.clear();
+        self.scores.clear();
+        self.visited.len() - before
+    }
+}
+
+/// How parents of the next generation are picked
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Selection {
+    /// uniformly from the `selection_size` best brains
+    #[default]
+    Truncation,
+    /// best of `selection_size` randomly picked brains
+    Tournament,
+    /// with probability proportional to the score
+    Roulette,
+    /// with probability proportional to the position in the ranking, the worst brain has weight 1
+    Rank,
+}
+
+impl Selection {
+    /// Picks one parent
+    ///
+    /// # Arguments
+    /// * `scores` - scores of all brains, the higher the better
+    /// * `candidates` - indexes of brains to pick from, ordered from the best to the worst
+    /// * `size` - number of best brains for truncation, size of the tournament
+    pub fn select(&self, scores: &[f64], candidates: &[usize], size: usize) -> usize {
+        let pick = |len: usize| (random() * len as f64) as usize % len;
+
+        match self {
+            Selection::Truncation => candidates[pick(size.clamp(1, candidates.len()))],
+            Selection::Tournament => (0..size.max(1))
+                .map(|_| candidates[pick(candidates.len())])
+                .max_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+                .unwrap(),
+            Selection::Roulette => {
+                // scores can be negative, so they are shifted to start at 1
+                let min = candidates
+                    .iter()
+                    .map(|c| scores[*c])
+                    .fold(f64::INFINITY, f64::min);
+                let weights = candidates.iter().map(|c| scores[*c] - min + 1.);
+                candidates[spin(weights.collect())]
+            }
+            Selection::Rank => {
+                let weights = (0..candidates.len()).rev().map(|r| (r + 1) as f64);
+                candidates[spin(weights.collect())]
+            }
+        }
+    }
+}
+
+/// Picks index with probability proportional to its weight
+fn spin(weights: Vec<f64>) -> usize {
+    let mut roll = random() * weights.iter().sum::<f64>();
+    for (i, weight) in weights.iter().enumerate() {
+        roll -= weight;
+        if roll < 0. {
+            return i;
+        }
+    }
+    weights.len() - 1
+}
+
+/// How the next generation is bred
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breeding {
+    pub selection: Selection,
+    pub selection_size: usize,
+    /// this many best brains are copied to the next generation unchanged
+    pub elitism: usize,
+    pub mutation: Mutation,
+}
+
+impl Breeding {
+    pub fn from_config(config: &Config) -> Self {
+        Breeding {
+            selection: config.selection,
+            selection_size: config.selection_size,
+            elitism: config.elitism,
+            mutation: Mutation::from_config(config),
+        }
+    }
+}
+
+/// Breeds new generation of brains
+///
+/// The best brains are copied unchanged, see [`Breeding::elitism`]. Rest of the places is split
+/// between species proportionally to the sum of their shared fitness and filled by mutated copies
+/// of parents selected within the species.
+///
+/// # Arguments
+/// * `brains` - brains of the finished generation
+/// * `scores` - score of each brain
+/// * `species` - indexes of brains of each species, see [`cluster`]
+/// * `size` - size of the new generation
+/// * `breeding` - how parents are selected and mutated
+pub fn breed(
+    brains: &[&NeuralNetwork],
+    scores: &[f64],
+    species: &[Vec<usize>],
+    size: usize,
+    breeding: &Breeding,
+) -> Vec<NeuralNetwork> {
+    let by_score = |a: &usize, b: &usize| scores[*b].total_cmp(&scores[*a]);
+
+    let mut ranking = (0..brains.len()).collect::<Vec<usize>>();
+    ranking.sort_by(by_score);
+    let mut children = ranking
+        .iter()
+        .take(breeding.elitism.min(size))
+        .map(|i| brains[*i].clone())
+        .collect::<Vec<NeuralNetwork>>();
+    let size = size - children.len();
+
+    let shared = shared_fitness(scores, species);
+    let totals = species
+        .iter()
+        .map(|members| members.iter().map(|m| shared[*m]).sum::<f64>())
+        .collect::<Vec<f64>>();
+    let total = totals.iter().sum::<f64>();
+
+    let mut offspring = totals
+        .iter()
+        .map(|t| (t / total * size as f64).floor() as usize)
+        .collect::<Vec<usize>>();
+    // places left after rounding down go to the best species
+    let best_species = totals
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i)
+        .unwrap_or_default();
+    let assigned = offspring.iter().sum::<usize>();
+    if let Some(o) = offspring.get_mut(best_species) {
+        *o += size.saturating_sub(assigned);
+    }
+
+    for (members, count) in species.iter().zip(offspring) {
+        if members.is_empty() {
+            continue;
+        }
+
+        let mut members = members.clone();
+        members.sort_by(by_score);
+
+        for _ in 0..count {
+            let parent = breeding
+                .selection
+                .select(scores, &members, breeding.selection_size);
+            children.push(brains[parent].mutate_with(&breeding.mutation));
+        }
+    }
+
+    children
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HallOfFameEntry {
+    #[serde(with = "super::format::compact")]
+    pub brain: NeuralNetwork,
+    pub fitness: f64,
+    pub generation: usize,
+}
+
+/// Best brains across all generations, ordered from the best
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct HallOfFame {
+    pub entries: Vec<HallOfFameEntry>,
+}
+
+impl HallOfFame {
+    /// Adds brain if it is better than any brain in the hall, the worst brains are dropped to keep at most `capacity` of them
+    pub fn submit(&mut self, entry: HallOfFameEntry, capacity: usize) {
+        let position = self
+            .entries
+            .iter()
+            .position(|e| e.fitness < entry.fitness)
+            .unwrap_or(self.entries.len());
+        if position >= capacity {
+            return;
+        }
+
+        self.entries.insert(position, entry);
+        self.entries.truncate(capacity);
+    }
+
+    pub fn best(&self) -> Option<&HallOfFameEntry> {
+        self.entries.first()
+    }
+}
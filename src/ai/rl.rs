@@ -0,0 +1,289 @@
+use std::ops::Deref;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use super::{
+    agents::fitness,
+    dataset::Dataset,
+    training::{Loss, Trainer},
+    Activation, NeuralNetwork,
+};
+use crate::{
+    car::Car,
+    scenario::{Progress, Scenario},
+    traffic::Traffic,
+    utils::random,
+    Config,
+};
+
+pub const GAMMA_DEFAULT: f64 = 0.99;
+pub const CRASH_PENALTY_DEFAULT: f64 = 100.;
+pub const MAX_TICKS_DEFAULT: usize = 3000;
+
+/// Result of one episode
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EpisodeStats {
+    pub ticks: usize,
+    /// same score the agents get in the simulation
+    pub fitness: f64,
+    /// sum of all rewards, ie progress made minus the crash penalty
+    #[wasm_bindgen(js_name = totalReward)]
+    pub total_reward: f64,
+    pub crashed: bool,
+    /// agent got past the last vehicle of the scenario
+    pub finished: bool,
+}
+
+/// Trains agent's brain with REINFORCE (Monte Carlo policy gradient)
+///
+/// Each output of the brain is treated as a probability of pressing the control, controls are sampled
+/// every tick and the agent is rewarded by progress it made since the last tick, crash is punished by
+/// `crash_penalty`. After every episode the brain is pushed towards actions which led to better than
+/// average returns.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct ReinforceTrainer {
+    policy: NeuralNetwork,
+    optimizer: Trainer,
+    config: Config,
+    scenarios: Vec<Scenario>,
+    /// discount of future rewards
+    pub gamma: f64,
+    #[wasm_bindgen(js_name = crashPenalty)]
+    pub crash_penalty: f64,
+    /// episode ends after this many ticks even if the agent did not crash
+    #[wasm_bindgen(js_name = maxTicks)]
+    pub max_ticks: usize,
+    episodes: usize,
+}
+
+#[wasm_bindgen]
+impl ReinforceTrainer {
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: &Config, learning_rate: f64) -> Self {
+        ReinforceTrainer {
+            policy: NeuralNetwork::trainable(config.neurons_count().deref()),
+            optimizer: Trainer::adam(learning_rate, 64).with_loss(Loss::CrossEntropy),
+            config: config.clone(),
+            scenarios: vec![Scenario::training()],
+            gamma: GAMMA_DEFAULT,
+            crash_penalty: CRASH_PENALTY_DEFAULT,
+            max_ticks: MAX_TICKS_DEFAULT,
+            episodes: 0,
+        }
+    }
+
+    /// Continues training from existing brain, brain's levels are switched to sigmoid so they can be trained
+    ///
+    /// # Returns
+    /// * `Result<(), String>` - error if the brain is recurrent, its memory can't be trained,
+    ///   or if it does not fit the sensor and controls of the config's agents
+    #[wasm_bindgen(js_name = setPolicy)]
+    pub fn set_policy(&mut self, policy: &NeuralNetwork) -> Result<(), String> {
+        if policy.is_recurrent() {
+            return Err("recurrent brain can't be trained with reinforcement learning".to_string());
+        }
+        let shape = (
+            policy.0.first().map(|level| level.input_count()),
+            policy.0.last().map(|level| level.output_count()),
+        );
+        if shape != (Some(self.config.rays_count), Some(4)) {
+            return Err(format!(
+                "brain has to have {} inputs and 4 outputs",
+                self.config.rays_count
+            ));
+        }
+        self.policy = policy.clone().with_activation(Activation::Sigmoid);
+        Ok(())
+    }
+
+    pub fn policy(&self) -> NeuralNetwork {
+        self.policy.clone()
+    }
+
+    /// Number of episodes trained so far
+    pub fn episodes(&self) -> usize {
+        self.episodes
+    }
+
+    #[wasm_bindgen(getter = learningRate)]
+    pub fn learning_rate(&self) -> f64 {
+        self.optimizer.learning_rate()
+    }
+
+    #[wasm_bindgen(setter = learningRate)]
+    pub fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.optimizer.set_learning_rate(learning_rate);
+    }
+
+    /// Runs `episodes` episodes, cycling through the scenarios, and updates the policy after each of them
+    ///
+    /// # Returns
+    /// * `f64` - mean fitness of the episodes
+    pub fn train(&mut self, episodes: usize) -> f64 {
+        let mut fitness = 0.;
+        for _ in 0..episodes {
+            fitness += self.train_episode().fitness;
+        }
+
+        fitness / episodes.max(1) as f64
+    }
+
+    /// Plays one episode with sampled controls and updates the policy
+    #[wasm_bindgen(js_name = trainEpisode)]
+    pub fn train_episode(&mut self) -> EpisodeStats {
+        let scenario = self.scenarios[self.episodes % self.scenarios.len()].clone();
+        self.episodes += 1;
+
+        let (dataset, rewards, stats) = self.play(&scenario, true);
+        if dataset.is_empty() {
+            return stats;
+        }
+
+        let advantages = normalize(discounted_returns(&rewards, self.gamma));
+        self.optimizer
+            .train_weighted(&mut self.policy, &dataset, &advantages)
+            .expect("policy is trainable and fits the samples it played");
+
+        stats
+    }
+
+    /// Plays one episode where controls are decided the same way as in the simulation, policy is not changed
+    pub fn evaluate(&self, scenario_index: usize) -> EpisodeStats {
+        let scenario = &self.scenarios[scenario_index % self.scenarios.len()];
+        self.play(scenario, false).2
+    }
+}
+
+impl ReinforceTrainer {
+    /// Config the agents are created from, it decides the shape of the policy
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Replaces scenarios the agent is trained on, there has to be at least one
+    pub fn set_scenarios(&mut self, scenarios: Vec<Scenario>) {
+        assert!(!scenarios.is_empty(), "at least one scenario is required");
+        self.scenarios = scenarios;
+    }
+
+    /// Simulates one episode without any canvas
+    ///
+    /// # Arguments
+    /// * `scenario` - traffic the agent has to get through
+    /// * `explore` - when set, controls are sampled from the policy's outputs, otherwise outputs are thresholded as in the simulation
+    ///
+    /// # Returns
+    /// * `Dataset` - what the agent saw and which controls it pressed each tick
+    /// * `Vec<f64>` - reward for each of the decisions
+    /// * `EpisodeStats` - summary of the episode
+    fn play(&self, scenario: &Scenario, explore: bool) -> (Dataset, Vec<f64>, EpisodeStats) {
+        let road = Scenario::road(&self.config);
+        let mut car = Car::ai_default(
+            0,
+            road.lane_center(self.config.lane_index as i32),
+            None,
+            &self.config,
+        );
+        // controls are decided by the policy, not by the car
+        car.set_brain(None);
+
+        let mut traffic = Traffic::new();
+        scenario.spawn(&road, &mut traffic, car.y);
+        let finish_y = scenario.finish_y(car.y);
+
+        let mut dataset = Dataset::new();
+        let mut rewards: Vec<f64> = vec![];
+        let mut last_fitness = fitness(&car);
+        let mut progress = Progress::new(&car);
+        let mut stats = EpisodeStats {
+            ticks: 0,
+            fitness: last_fitness,
+            total_reward: 0.,
+            crashed: false,
+            finished: false,
+        };
+
+        while stats.ticks < self.max_ticks {
+            stats.ticks += 1;
+            traffic.update();
+            car.update(&road, &traffic);
+
+            // reward belongs to the decision made in previous tick as that is what moved the car
+            let current_fitness = fitness(&car);
+            let mut reward = current_fitness - last_fitness;
+            last_fitness = current_fitness;
+
+            if car.damaged {
+                reward -= self.crash_penalty;
+                stats.crashed = true;
+            }
+
+            if let Some(last) = rewards.last_mut() {
+                *last += reward;
+                stats.total_reward += reward;
+            }
+
+            if car.damaged {
+                break;
+            }
+
+            if car.y < finish_y {
+                stats.finished = true;
+                break;
+            }
+
+            // episode ends the same way as the evaluation of the scenario
+            if progress.is_stalled(stats.ticks, &car) {
+                break;
+            }
+
+            let inputs = car.sensor_inputs().expect("agent without sensor");
+            let probabilities = self.policy.forward(&inputs);
+            let actions = probabilities
+                .iter()
+                .map(|p| match explore {
+                    true => (random() < *p) as u8 as f64,
+                    false => (*p > 0.5) as u8 as f64,
+                })
+                .collect::<Vec<f64>>();
+
+            car.set_controls(
+                actions[0] == 1.,
+                actions[1] == 1.,
+                actions[2] == 1.,
+                actions[3] == 1.,
+            );
+            dataset.push(inputs, actions);
+            rewards.push(0.);
+        }
+
+        stats.fitness = last_fitness;
+        (dataset, rewards, stats)
+    }
+}
+
+/// Sum of rewards from each tick until the end of the episode, future rewards are discounted by `gamma`
+pub fn discounted_returns(rewards: &[f64], gamma: f64) -> Vec<f64> {
+    let mut returns = vec![0.; rewards.len()];
+    let mut running = 0.;
+    for (i, reward) in rewards.iter().enumerate().rev() {
+        running = reward + gamma * running;
+        returns[i] = running;
+    }
+    returns
+}
+
+/// Shifts values to zero mean and unit variance, so roughly half of the decisions are encouraged
+/// and half discouraged no matter the scale of rewards
+fn normalize(values: Vec<f64>) -> Vec<f64> {
+    let count = values.len().max(1) as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let std = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count).sqrt();
+
+    match std > f64::EPSILON {
+        true => values.into_iter().map(|v| (v - mean) / std).collect(),
+        false => values.into_iter().map(|v| v - mean).collect(),
+    }
+}
@@ -0,0 +1,437 @@
+use super::{dataset::Dataset, Activation, NeuralNetwork};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+/// Guards logarithms in cross entropy against outputs that are exactly 0 or 1
+const EPSILON: f64 = 1e-12;
+
+/// Measures how far network's outputs are from the expected ones
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Loss {
+    #[default]
+    MeanSquaredError,
+    /// binary cross entropy, each output is treated as independent probability of the control being pressed
+    CrossEntropy,
+}
+
+impl Loss {
+    /// Mean loss over all outputs of one sample
+    pub fn value(&self, outputs: &[f64], targets: &[f64]) -> f64 {
+        let sum = outputs
+            .iter()
+            .zip(targets.iter())
+            .map(|(output, target)| match self {
+                Loss::MeanSquaredError => (output - target).powi(2),
+                Loss::CrossEntropy => {
+                    let output = output.clamp(EPSILON, 1. - EPSILON);
+                    -(target * output.ln() + (1. - target) * (1. - output).ln())
+                }
+            })
+            .sum::<f64>();
+
+        sum / outputs.len().max(1) as f64
+    }
+
+    /// Gradient of the loss with respect to the weighted sum (before activation) of one output neuron
+    fn output_delta(&self, output: f64, target: f64, activation: Activation, count: usize) -> f64 {
+        let count = count.max(1) as f64;
+        match self {
+            Loss::MeanSquaredError => {
+                2. * (output - target) * activation.derivative(output) / count
+            }
+            // derivative of sigmoid cancels out with the derivative of cross entropy, which keeps the
+            // gradient from vanishing when the output saturates
+            Loss::CrossEntropy => (output - target) / count,
+        }
+    }
+}
+
+/// How are the gradients turned into changes of weights and biases
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Optimizer {
+    /// stochastic gradient descent, with momentum of 0 it is the plain one
+    Sgd { momentum: f64 },
+    Adam {
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+    },
+}
+
+/// Trains [`NeuralNetwork`] with backpropagation on mini-batches of samples
+///
+/// Trainer keeps state of the optimizer, so it should be used with one network only.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Trainer {
+    loss: Loss,
+    optimizer: Optimizer,
+    learning_rate: f64,
+    batch_size: usize,
+    /// velocity for SGD with momentum, first moment for Adam
+    first_moment: Vec<f64>,
+    /// second moment for Adam
+    second_moment: Vec<f64>,
+    /// number of optimizer steps taken so far, used by Adam's bias correction
+    steps: i32,
+}
+
+#[wasm_bindgen]
+impl Trainer {
+    /// # Arguments
+    /// * `learning_rate` - size of the step taken against the gradient
+    /// * `momentum` - how much of the previous step is carried into the next one, `0` for plain SGD
+    /// * `batch_size` - number of samples averaged into one step
+    pub fn sgd(learning_rate: f64, momentum: f64, batch_size: usize) -> Self {
+        Trainer::new(Optimizer::Sgd { momentum }, learning_rate, batch_size)
+    }
+
+    /// Adam with the usual defaults, betas `0.9` and `0.999`
+    pub fn adam(learning_rate: f64, batch_size: usize) -> Self {
+        Trainer::new(
+            Optimizer::Adam {
+                beta1: 0.9,
+                beta2: 0.999,
+                epsilon: 1e-8,
+            },
+            learning_rate,
+            batch_size,
+        )
+    }
+
+    #[wasm_bindgen(js_name = withLoss)]
+    pub fn with_loss(mut self, loss: Loss) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    #[wasm_bindgen(getter = learningRate)]
+    pub fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    /// Changes size of the following steps, state of the optimizer is kept
+    #[wasm_bindgen(setter = learningRate)]
+    pub fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate;
+    }
+
+    /// Trains the network on the dataset, samples are shuffled every epoch
+    ///
+    /// Step activation has no usable gradient, so train networks with [`Activation::Sigmoid`].
+    ///
+    /// # Returns
+    /// * `Result<f64, String>` - mean loss of the last epoch, error if the network is recurrent,
+    ///   uses step activation or the samples do not match its inputs and outputs
+    pub fn train(
+        &mut self,
+        network: &mut NeuralNetwork,
+        dataset: &Dataset,
+        epochs: usize,
+    ) -> Result<f64, String> {
+        check_trainable(network, dataset, None)?;
+
+        let mut epoch_loss = 0.;
+        let mut order = (0..dataset.len()).collect::<Vec<usize>>();
+        let mut gradients = vec![0.; network.parameters_count()];
+
+        for _ in 0..epochs {
+            shuffle(&mut order);
+            epoch_loss = 0.;
+
+            for batch in order.chunks(self.batch_size.max(1)) {
+                gradients.iter_mut().for_each(|g| *g = 0.);
+
+                for index in batch.iter() {
+                    let sample = &dataset.samples[*index];
+                    epoch_loss += network.accumulate_gradients(
+                        &sample.inputs,
+                        &sample.outputs,
+                        self.loss,
+                        1.,
+                        &mut gradients,
+                    );
+                }
+
+                let batch_len = batch.len() as f64;
+                gradients.iter_mut().for_each(|g| *g /= batch_len);
+                self.step(network, &gradients);
+            }
+
+            epoch_loss /= dataset.len().max(1) as f64;
+        }
+
+        Ok(epoch_loss)
+    }
+
+    /// Mean loss of the network over the whole dataset, network is not changed
+    pub fn evaluate(&self, network: &NeuralNetwork, dataset: &Dataset) -> f64 {
+        let sum = dataset
+            .samples
+            .iter()
+            .map(|sample| {
+                let activations = network.activations(&sample.inputs);
+                let outputs = activations
+                    .last()
+                    .expect("activations start with the inputs");
+                self.loss.value(outputs, &sample.outputs)
+            })
+            .sum::<f64>();
+
+        sum / dataset.len().max(1) as f64
+    }
+}
+
+impl Trainer {
+    /// One pass over the dataset where gradient of each sample is scaled by its weight,
+    /// samples with negative weight push the network away from their outputs
+    ///
+    /// This is what policy gradient needs, outputs are the actions taken and weights are their advantages.
+    ///
+    /// # Returns
+    /// * `Result<f64, String>` - mean (unweighted) loss of the samples, error if the network can't be
+    ///   trained on the samples or there is not one weight per sample
+    pub fn train_weighted(
+        &mut self,
+        network: &mut NeuralNetwork,
+        dataset: &Dataset,
+        weights: &[f64],
+    ) -> Result<f64, String> {
+        check_trainable(network, dataset, Some(weights))?;
+
+        let mut loss = 0.;
+        let mut gradients = vec![0.; network.parameters_count()];
+        let indexes = (0..dataset.len()).collect::<Vec<usize>>();
+
+        for batch in indexes.chunks(self.batch_size.max(1)) {
+            gradients.iter_mut().for_each(|g| *g = 0.);
+
+            for index in batch.iter() {
+                let sample = &dataset.samples[*index];
+                loss += network.accumulate_gradients(
+                    &sample.inputs,
+                    &sample.outputs,
+                    self.loss,
+                    weights[*index],
+                    &mut gradients,
+                );
+            }
+
+            let batch_len = batch.len() as f64;
+            gradients.iter_mut().for_each(|g| *g /= batch_len);
+            self.step(network, &gradients);
+        }
+
+        Ok(loss / dataset.len().max(1) as f64)
+    }
+
+    fn new(optimizer: Optimizer, learning_rate: f64, batch_size: usize) -> Self {
+        Trainer {
+            loss: Loss::default(),
+            optimizer,
+            learning_rate,
+            batch_size,
+            first_moment: vec![],
+            second_moment: vec![],
+            steps: 0,
+        }
+    }
+
+    /// Applies averaged gradients of one batch to the network
+    fn step(&mut self, network: &mut NeuralNetwork, gradients: &[f64]) {
+        // network's shape changed or this is the first step, we need to start from scratch
+        if self.first_moment.len() != gradients.len() {
+            self.first_moment = vec![0.; gradients.len()];
+            self.second_moment = vec![0.; gradients.len()];
+            self.steps = 0;
+        }
+        self.steps += 1;
+
+        let learning_rate = self.learning_rate;
+        let parameters = network.parameters_mut().zip(gradients.iter()).zip(
+            self.first_moment
+                .iter_mut()
+                .zip(self.second_moment.iter_mut()),
+        );
+
+        match self.optimizer {
+            Optimizer::Sgd { momentum } => {
+                for ((parameter, gradient), (velocity, _)) in parameters {
+                    *velocity = momentum * *velocity - learning_rate * gradient;
+                    *parameter += *velocity;
+                }
+            }
+            Optimizer::Adam {
+                beta1,
+                beta2,
+                epsilon,
+            } => {
+                let first_correction = 1. - beta1.powi(self.steps);
+                let second_correction = 1. - beta2.powi(self.steps);
+                for ((parameter, gradient), (first, second)) in parameters {
+                    *first = beta1 * *first + (1. - beta1) * gradient;
+                    *second = beta2 * *second + (1. - beta2) * gradient * gradient;
+                    let first_hat = *first / first_correction;
+                    let second_hat = *second / second_correction;
+                    *parameter -= learning_rate * first_hat / (second_hat.sqrt() + epsilon);
+                }
+            }
+        }
+    }
+}
+
+impl NeuralNetwork {
+    /// Forward pass that keeps activations of every level, first item are the inputs, last are network's outputs
+    pub(crate) fn activations(&self, inputs: &[f64]) -> Vec<Vec<f64>> {
+        let mut activations = Vec::with_capacity(self.0.len() + 1);
+        activations.push(inputs.to_vec());
+
+        for level in self.0.iter() {
+            let level_inputs = activations
+                .last()
+                .expect("activations start with the inputs");
+            let mut outputs = vec![0.; level.output_count()];
+            level.compute(level_inputs, &mut outputs);
+            activations.push(outputs);
+        }
+
+        activations
+    }
+
+    /// Fits the network to the recorded samples using plain stochastic gradient descent
+    /// with mean squared error loss, see [`Trainer`] for other options
+    ///
+    /// # Returns
+    /// * `Result<f64, String>` - mean loss of the last epoch, error if the network can't be trained on the samples
+    pub fn fit(
+        &mut self,
+        dataset: &Dataset,
+        epochs: usize,
+        learning_rate: f64,
+    ) -> Result<f64, String> {
+        Trainer::sgd(learning_rate, 0., 1).train(self, dataset, epochs)
+    }
+
+    /// Backpropagates error of one sample and adds the gradient of each parameter, scaled by `weight`,
+    /// to `gradients`, gradients are in the same order as [`NeuralNetwork::parameters_mut`]
+    ///
+    /// # Returns
+    /// * `f64` - loss of the sample
+    fn accumulate_gradients(
+        &self,
+        inputs: &[f64],
+        targets: &[f64],
+        loss: Loss,
+        weight: f64,
+        gradients: &mut [f64],
+    ) -> f64 {
+        let activations = self.activations(inputs);
+        let outputs = activations
+            .last()
+            .expect("activations start with the inputs");
+        let loss_value = loss.value(outputs, targets);
+
+        // where parameters of each level start in the gradients
+        let offsets = self
+            .0
+            .iter()
+            .scan(0, |offset, level| {
+                let start = *offset;
+                *offset += level.parameters_count();
+                Some(start)
+            })
+            .collect::<Vec<usize>>();
+
+        // error of each output of the level we are currently processing, starting with the last one
+        let last_activation = self.0.last().expect("missing output level").activation;
+        let mut deltas = outputs
+            .iter()
+            .zip(targets.iter())
+            .map(|(output, target)| {
+                weight * loss.output_delta(*output, *target, last_activation, outputs.len())
+            })
+            .collect::<Vec<f64>>();
+
+        for (index, level) in self.0.iter().enumerate().rev() {
+            let level_inputs = &activations[index];
+            let inputs_count = level_inputs.len();
+            let offset = offsets[index];
+
+            // weights are stored output by output
+            for (j, delta) in deltas.iter().enumerate() {
+                for (i, input) in level_inputs.iter().enumerate() {
+                    gradients[offset + j * inputs_count + i] += input * delta;
+                }
+            }
+
+            let biases_offset = offset + level.weights.len();
+            for (j, delta) in deltas.iter().enumerate() {
+                gradients[biases_offset + j] += delta;
+            }
+
+            if index == 0 {
+                break;
+            }
+
+            let previous_activation = self.0[index - 1].activation;
+            deltas = level_inputs
+                .iter()
+                .enumerate()
+                .map(|(i, input)| {
+                    let error = deltas
+                        .iter()
+                        .enumerate()
+                        .map(|(j, delta)| level.weight(i, j) * delta)
+                        .sum::<f64>();
+                    error * previous_activation.derivative(*input)
+                })
+                .collect::<Vec<f64>>();
+        }
+
+        loss_value
+    }
+}
+
+/// Checks the network can be trained on the dataset before any of its parameters is changed
+///
+/// Backpropagation does not go back through the previous ticks, recurrent weights would never
+/// be trained and memory would be treated as a constant, so recurrent networks are rejected.
+/// Step activation has no usable gradient, so networks with step levels are rejected too.
+/// Samples of different width than the network would write gradients into wrong parameters.
+fn check_trainable(
+    network: &NeuralNetwork,
+    dataset: &Dataset,
+    weights: Option<&[f64]>,
+) -> Result<(), String> {
+    let (Some(first), Some(last)) = (network.0.first(), network.0.last()) else {
+        return Err("network has no levels".to_string());
+    };
+    if network.is_recurrent() {
+        return Err("recurrent networks can't be trained with backpropagation".to_string());
+    }
+    if network
+        .0
+        .iter()
+        .any(|l| l.activation != Activation::Sigmoid)
+    {
+        return Err("only networks with sigmoid activation can be trained".to_string());
+    }
+    dataset.validate(first.input_count(), last.output_count())?;
+
+    match weights {
+        Some(weights) if weights.len() != dataset.len() => Err(format!(
+            "got {} weights for {} samples",
+            weights.len(),
+            dataset.len()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Fisher-Yates shuffle
+fn shuffle(items: &mut [usize]) {
+    for i in (1..items.len()).rev() {
+        let j = (crate::utils::random() * (i + 1) as f64) as usize;
+        items.swap(i, j.min(i));
+    }
+}
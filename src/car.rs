@@ -1,9 +1,13 @@
 use crate::{
-    ai::NeuralNetwork,
-    controls::{ControlType, Controls},
+    ai::{mutation::Mutation, neat::Genome, NeuralNetwork},
+    controls::{ControlType, Controls, KeyEvent},
+    dynamics::{BicycleModel, Dynamics},
+    events::CrashCause,
+    replay::{CarKind, CarPose},
     road::Road,
     sensors::Sensor,
     traffic::Traffic,
+    vehicle::VehicleType,
     Config,
 };
 use std::ops::Neg;
@@ -18,7 +22,7 @@ const ACCELERATION: f64 = 0.2;
 // const RAYS_COUNT: usize = 5;
 
 #[wasm_bindgen]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Car {
     #[wasm_bindgen(skip)]
     pub id: usize,
@@ -29,11 +33,17 @@ pub struct Car {
     speed: f64,
     max_speed: f64,
     angle: f64,
+    dynamics: Dynamics,
+    vehicle_type: VehicleType,
     controls: Controls,
     sensor: Option<Sensor>,
     brain: Option<NeuralNetwork>,
+    genome: Option<Genome>,
     polygons: Vec<(f64, f64)>,
     pub damaged: bool,
+    /// what the car crashed into, set together with `damaged`
+    #[serde(default)]
+    crash: Option<CrashCause>,
 }
 
 #[wasm_bindgen]
@@ -52,6 +62,23 @@ impl Car {
         )
     }
 
+    /// Creates traffic vehicle of given type, its dimensions and max speed are resolved from the type
+    pub fn vehicle(vehicle_type: VehicleType, x: f64, y: f64) -> Self {
+        let mut car = Car::new(
+            0,
+            x,
+            y,
+            vehicle_type.width(),
+            vehicle_type.height(),
+            Controls::default(),
+            vehicle_type.max_speed(),
+            None,
+            None,
+        );
+        car.vehicle_type = vehicle_type;
+        car
+    }
+
     pub fn with_brain(
         id: usize,
         x: f64,
@@ -79,12 +106,23 @@ impl Car {
         self.brain = brain;
     }
 
-    /*     pub fn handle_key_input(&mut self, event: KeyEvent) {
-           if let ControlType::Keyboard = self.controls.control_type {
-               self.controls.handle_key_input(event);
-           }
-       }
-    */
+    /// Switches car to the kinematic bicycle model steering
+    #[wasm_bindgen(js_name = useBicycleModel)]
+    pub fn use_bicycle_model(&mut self, model: BicycleModel) {
+        self.dynamics = Dynamics::Bicycle(model);
+    }
+
+    /// Switches car back to the default steering where heading changes by constant angle
+    #[wasm_bindgen(js_name = useArcadeModel)]
+    pub fn use_arcade_model(&mut self) {
+        self.dynamics = Dynamics::Arcade;
+    }
+
+    pub fn handle_key_input(&mut self, event: KeyEvent) {
+        if let ControlType::Keyboard = self.controls.control_type {
+            self.controls.handle_key_input(event);
+        }
+    }
 
     pub fn mutate(&mut self, mutation: f64) {
         self.brain = self.brain.take().map(|brain| brain.mutate(mutation));
@@ -94,30 +132,18 @@ impl Car {
         self.move_car();
 
         self.create_polygon();
-        self.damaged = self.resolve_damage(road, traffic);
+        self.crash = self.resolve_damage(road, traffic);
+        self.damaged = self.crash.is_some();
 
         if let Some(sensor) = self.sensor.as_mut() {
             sensor.update(self.x, self.y, self.angle, road.boarders(), traffic);
+        }
 
-            if let Some(brain) = self.brain.as_mut() {
-                let offsets = sensor
-                    .readings()
-                    .iter()
-                    .map(|x| x.map(|i| 1. - i.offset).unwrap_or_default())
-                    .collect::<Vec<f64>>();
-
-                brain.feed_forward_2(offsets);
-                let outputs = brain
-                    .0
-                    .last()
-                    .expect("missing ouput layer")
-                    .outputs
-                    .borrow();
-
-                self.controls.up = outputs[0] == 1.;
-                self.controls.left = outputs[1] == 1.;
-                self.controls.right = outputs[2] == 1.;
-                self.controls.down = outputs[3] == 1.;
+        if let Some(offsets) = self.sensor_inputs() {
+            match (self.brain.as_mut(), self.genome.as_ref()) {
+                (Some(brain), _) => self.controls.set_from_outputs(brain.feed_forward(&offsets)),
+                (None, Some(genome)) => self.controls.set_from_outputs(&genome.activate(&offsets)),
+                _ => (),
             }
         }
     }
@@ -129,10 +155,10 @@ impl Car {
 
     pub fn draw(&self, ctx: &CanvasRenderingContext2d, draw_sensor: bool) {
         match (self.damaged, self.controls.control_type) {
-            (true, _) => ctx.set_fill_style(&JsValue::from_str("gray")),
-            (false, ControlType::Keyboard) => ctx.set_fill_style(&JsValue::from_str("blue")),
-            (false, ControlType::NoControl) => ctx.set_fill_style(&JsValue::from_str("red")),
-            _ => ctx.set_fill_style(&JsValue::from_str("cyan")),
+            (true, _) => ctx.set_fill_style_str("gray"),
+            (false, ControlType::Keyboard) => ctx.set_fill_style_str("blue"),
+            (false, ControlType::NoControl) => ctx.set_fill_style_str(self.vehicle_type.color()),
+            _ => ctx.set_fill_style_str("cyan"),
         };
 
         ctx.begin_path();
@@ -146,6 +172,26 @@ impl Car {
 
         ctx.fill();
 
+        // polygon goes around the car as top right, top left, bottom left, bottom right
+        // so we can find the cab line by going from the front corners to the back ones
+        if let (Some(ratio), [top_right, top_left, bottom_left, bottom_right]) =
+            (self.vehicle_type.cab_ratio(), self.polygons.deref())
+        {
+            let lerp = crate::utils::lerp;
+            ctx.begin_path();
+            ctx.set_line_width(2.);
+            ctx.set_stroke_style_str("black");
+            ctx.move_to(
+                lerp(top_right.0, bottom_right.0, ratio),
+                lerp(top_right.1, bottom_right.1, ratio),
+            );
+            ctx.line_to(
+                lerp(top_left.0, bottom_left.0, ratio),
+                lerp(top_left.1, bottom_left.1, ratio),
+            );
+            ctx.stroke();
+        }
+
         match self.sensor.as_ref() {
             Some(sensor) if draw_sensor => sensor.draw(ctx),
             _ => (),
@@ -173,17 +219,30 @@ impl Car {
             height,
             speed: 0.0,
             angle: 0.0,
+            dynamics: Dynamics::default(),
+            vehicle_type: VehicleType::default(),
             controls,
             sensor,
             brain,
+            genome: None,
             polygons: vec![],
             damaged: false,
+            crash: None,
             max_speed,
         }
     }
 
     pub fn ai_default(id: usize, lane: f64, brain: Option<NeuralNetwork>, config: &Config) -> Self {
-        Car::with_brain(
+        // brain starts driving a new car, so it must not remember what the previous one saw
+        let brain = match brain {
+            Some(mut brain) => {
+                brain.reset_memory();
+                brain
+            }
+            None => NeuralNetwork::from_config(config),
+        };
+
+        let mut car = Car::with_brain(
             id,
             lane,
             crate::CAR_Y_DEFAULT,
@@ -195,14 +254,180 @@ impl Car {
                 std::f64::consts::PI / config.rays_spread,
             ),
             config.neurons_count().deref(),
-            brain,
+            Some(brain),
+        );
+
+        if config.bicycle_model {
+            car.use_bicycle_model(BicycleModel::default());
+        }
+
+        car
+    }
+
+    pub fn dynamics(&self) -> &Dynamics {
+        &self.dynamics
+    }
+
+    pub fn vehicle_type(&self) -> VehicleType {
+        self.vehicle_type
+    }
+
+    pub fn x(&self) -> f64 {
+        self.x
+    }
+
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// Creates car driven by a human, it has same dimensions and sensor as AI agents so the race is fair
+    pub fn human(id: usize, lane: f64, config: &Config) -> Self {
+        let mut car = Car::new(
+            id,
+            lane,
+            crate::CAR_Y_DEFAULT,
+            crate::CAR_WIDHT_DEFAULT,
+            crate::CAR_HEIGHT_DEFAULT,
+            Controls::new(ControlType::Keyboard),
+            3.0,
+            Some(Sensor::new(
+                config.rays_count as i32,
+                config.rays_lenght,
+                std::f64::consts::PI / config.rays_spread,
+            )),
+            None,
+        );
+
+        if config.bicycle_model {
+            car.use_bicycle_model(BicycleModel::default());
+        }
+
+        car
+    }
+
+    /// Position and controls of the car as recorded by [`crate::replay::Replay`]
+    pub fn pose(&self) -> CarPose {
+        let kind = match self.controls.control_type {
+            ControlType::Keyboard => CarKind::Human,
+            ControlType::NoControl => CarKind::Traffic(self.vehicle_type),
+            ControlType::Ai => CarKind::Agent,
+        };
+
+        CarPose::new(
+            self.id as u32,
+            kind,
+            (self.x, self.y, self.angle),
+            [
+                self.controls.up,
+                self.controls.left,
+                self.controls.right,
+                self.controls.down,
+            ],
+            self.damaged,
         )
     }
 
+    /// Car standing in the recorded pose, it is only meant to be drawn
+    pub fn from_pose(pose: &CarPose) -> Self {
+        let control_type = match pose.kind {
+            CarKind::Agent => ControlType::Ai,
+            CarKind::Human => ControlType::Keyboard,
+            CarKind::Traffic(_) => ControlType::NoControl,
+        };
+        let mut car = match pose.kind {
+            CarKind::Traffic(vehicle_type) => {
+                Car::vehicle(vehicle_type, pose.x as f64, pose.y as f64)
+            }
+            _ => Car::new(
+                pose.id as usize,
+                pose.x as f64,
+                pose.y as f64,
+                crate::CAR_WIDHT_DEFAULT,
+                crate::CAR_HEIGHT_DEFAULT,
+                Controls::new(control_type),
+                0.,
+                None,
+                None,
+            ),
+        };
+
+        let [up, left, right, down] = pose.controls();
+        car.set_controls(up, left, right, down);
+        car.angle = pose.angle as f64;
+        car.damaged = pose.damaged();
+        car.create_polygon();
+        car
+    }
+
+    /// What the car crashed into, `None` while it is not damaged
+    pub fn crash(&self) -> Option<CrashCause> {
+        self.crash
+    }
+
+    pub fn is_human(&self) -> bool {
+        matches!(self.controls.control_type, ControlType::Keyboard)
+    }
+
+    /// Sensor readings turned into brain inputs, closer the obstacle is, closer the value is to 1
+    pub fn sensor_inputs(&self) -> Option<Vec<f64>> {
+        self.sensor.as_ref().map(|sensor| {
+            sensor
+                .readings()
+                .iter()
+                .map(|x| x.map(|i| 1. - i.offset).unwrap_or_default())
+                .collect::<Vec<f64>>()
+        })
+    }
+
+    pub fn controls(&self) -> &Controls {
+        &self.controls
+    }
+
+    pub fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.controls
+    }
+
+    /// Overrides controls, for when something else than car's own brain decides where to go
+    pub fn set_controls(&mut self, up: bool, left: bool, right: bool, down: bool) {
+        self.controls.up = up;
+        self.controls.left = left;
+        self.controls.right = right;
+        self.controls.down = down;
+    }
+
     pub fn brain(&self) -> Option<&NeuralNetwork> {
         self.brain.as_ref()
     }
 
+    pub fn brain_mut(&mut self) -> Option<&mut NeuralNetwork> {
+        self.brain.as_mut()
+    }
+
+    /// AI car driven by NEAT genome instead of neural network
+    pub fn with_genome(id: usize, lane: f64, genome: Genome, config: &Config) -> Self {
+        let mut car = Car::ai_default(id, lane, None, config);
+        car.brain = None;
+        car.genome = Some(genome);
+        car
+    }
+
+    pub fn genome(&self) -> Option<&Genome> {
+        self.genome.as_ref()
+    }
+
+    /// Generates car for each of the brains, id of the car is index of its brain
+    pub fn generate_cars(x: f64, brains: Vec<NeuralNetwork>, config: &Config) -> Vec<Car> {
+        brains
+            .into_iter()
+            .enumerate()
+            .map(|(id, brain)| Car::ai_default(id, x, Some(brain), config))
+            .collect()
+    }
+
     /// Generates vector of cars that will differ only in their brains
     /// # Arguments
     /// * `count` - number of cars to generate
@@ -222,7 +447,9 @@ impl Car {
             cars.push(Car::ai_default(
                 n,
                 x,
-                brain.clone().map(|b| b.mutate(config.mutation_rate)),
+                brain
+                    .clone()
+                    .map(|b| b.mutate_with(&Mutation::from_config(config))),
                 config,
             ))
         });
@@ -239,14 +466,21 @@ impl Car {
             self.speed -= ACCELERATION;
         }
 
-        if self.speed != 0. {
-            let flip = if self.speed > 0. { 1. } else { -1. };
-            if self.controls.left() {
-                self.angle += ANGLE_TURN * flip;
-            }
+        match self.dynamics {
+            Dynamics::Arcade if self.speed != 0. => {
+                let flip = if self.speed > 0. { 1. } else { -1. };
+                if self.controls.left() {
+                    self.angle += ANGLE_TURN * flip;
+                }
 
-            if self.controls.right() {
-                self.angle -= ANGLE_TURN * flip;
+                if self.controls.right() {
+                    self.angle -= ANGLE_TURN * flip;
+                }
+            }
+            Dynamics::Arcade => (),
+            // bicycle model already accounts for the direction of travel as the heading change is scaled by speed
+            Dynamics::Bicycle(ref mut model) => {
+                self.angle += model.steer(self.controls.left(), self.controls.right(), self.speed);
             }
         }
 
@@ -308,17 +542,23 @@ impl Car {
         self.polygons.deref()
     }
 
-    fn resolve_damage(&mut self, road: &Road, traffic: &Traffic) -> bool {
+    fn resolve_damage(&mut self, road: &Road, traffic: &Traffic) -> Option<CrashCause> {
         if crate::utils::poly_intersection_with_borders(self.polygons.deref(), road.boarders()) {
-            return true;
+            return Some(CrashCause::Border);
         };
 
-        traffic.0.iter().any(|car| {
-            match (self.y.abs() - car.y.abs()).abs() > 100. {
-                // if car not in range, no damage can be done
-                true => false,
-                false => crate::utils::poly_intersection_with_poly(self.polygons(), car.polygons()),
-            }
-        })
+        traffic
+            .0
+            .iter()
+            .find(|car| {
+                match (self.y.abs() - car.y.abs()).abs() > (self.height + car.height) / 2. + 50. {
+                    // if car not in range, no damage can be done
+                    true => false,
+                    false => {
+                        crate::utils::poly_intersection_with_poly(self.polygons(), car.polygons())
+                    }
+                }
+            })
+            .map(|car| CrashCause::Car(car.id))
     }
 }
@@ -9,7 +9,7 @@ pub enum ControlType {
 }
 
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum KeyEvent {
     UpPressed,
     UpReleased,
@@ -22,7 +22,7 @@ pub enum KeyEvent {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct Controls {
     pub(crate) control_type: ControlType,
     pub up: bool,
@@ -94,6 +94,23 @@ impl Controls {
     pub fn left(&self) -> bool {
         self.left
     }
+
+    /// Controls in the same order as the outputs of the brain, pressed is 1, released 0
+    pub fn as_outputs(&self) -> Vec<f64> {
+        [self.up, self.left, self.right, self.down]
+            .iter()
+            .map(|pressed| *pressed as u8 as f64)
+            .collect()
+    }
+
+    /// Presses controls whose brain outputs are over the half, inverse of [`Controls::as_outputs`]
+    pub fn set_from_outputs(&mut self, outputs: &[f64]) {
+        // step outputs exactly 0 or 1, sigmoid anything in between so we threshold in the middle
+        self.up = outputs[0] > 0.5;
+        self.left = outputs[1] > 0.5;
+        self.right = outputs[2] > 0.5;
+        self.down = outputs[3] > 0.5;
+    }
 }
 
 impl std::default::Default for Controls {
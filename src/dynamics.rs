@@ -0,0 +1,106 @@
+use wasm_bindgen::prelude::*;
+
+pub const WHEELBASE_DEFAULT: f64 = 35.;
+/// roughly 20 degrees, at max speed this turns about as fast as the arcade steering
+pub const MAX_STEERING_ANGLE_DEFAULT: f64 = 0.35;
+pub const STEERING_RATE_DEFAULT: f64 = 0.04;
+/// shorter wheelbase would make the car spin in place, zero would make its heading infinite
+pub const WHEELBASE_MIN: f64 = 1.;
+
+/// Decides how steering input is turned into change of car's heading
+#[derive(Debug, Clone, Copy, PartialEq, Default, serde::Serialize, serde::Deserialize)]
+pub enum Dynamics {
+    /// Heading changes by constant angle each tick while car is moving, no matter the speed
+    #[default]
+    Arcade,
+    /// Kinematic bicycle model, heading changes based on speed, wheelbase and steering angle
+    Bicycle(BicycleModel),
+}
+
+/// Kinematic bicycle model
+///
+/// Front wheel is steered towards the lock by `steering_rate` each tick while steering key is held
+/// and returns back to the center when released. Heading then changes by `speed / wheelbase * tan(steering_angle)`,
+/// so the turning radius is `wheelbase / tan(steering_angle)` and slow car turns less per tick than the fast one.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct BicycleModel {
+    /// distance between front and rear axle
+    pub wheelbase: f64,
+    /// maximum steering angle (lock) in radians
+    #[wasm_bindgen(js_name = maxSteeringAngle)]
+    pub max_steering_angle: f64,
+    /// how much steering angle can change in one tick, in radians
+    #[wasm_bindgen(js_name = steeringRate)]
+    pub steering_rate: f64,
+    steering_angle: f64,
+}
+
+#[wasm_bindgen]
+impl BicycleModel {
+    /// Wheelbase shorter than [`WHEELBASE_MIN`] or not a number is clamped to it
+    #[wasm_bindgen(constructor)]
+    pub fn new(wheelbase: f64, max_steering_angle: f64, steering_rate: f64) -> Self {
+        BicycleModel {
+            wheelbase: wheelbase.max(WHEELBASE_MIN),
+            max_steering_angle: max_steering_angle.abs(),
+            steering_rate: steering_rate.abs(),
+            steering_angle: 0.,
+        }
+    }
+
+    /// Current angle of the front wheel, positive means steering to the left
+    #[wasm_bindgen(getter = steeringAngle)]
+    pub fn steering_angle(&self) -> f64 {
+        self.steering_angle
+    }
+}
+
+impl BicycleModel {
+    /// Updates steering angle based on pressed controls and returns change of heading for this tick
+    ///
+    /// # Arguments
+    /// * `left` - is steering to the left
+    /// * `right` - is steering to the right
+    /// * `speed` - current speed of the car, negative when reversing
+    pub fn steer(&mut self, left: bool, right: bool, speed: f64) -> f64 {
+        match (left, right) {
+            (true, false) => self.steering_angle += self.steering_rate,
+            (false, true) => self.steering_angle -= self.steering_rate,
+            // no steering input, wheel returns back to the center
+            _ if self.steering_angle > 0. => {
+                self.steering_angle = (self.steering_angle - self.steering_rate).max(0.)
+            }
+            _ => self.steering_angle = (self.steering_angle + self.steering_rate).min(0.),
+        }
+
+        self.steering_angle = self
+            .steering_angle
+            .clamp(-self.max_steering_angle, self.max_steering_angle);
+
+        speed / self.clamped_wheelbase() * self.steering_angle.tan()
+    }
+
+    /// Radius of the circle the car follows with current steering angle, `None` when driving straight
+    pub fn turning_radius(&self) -> Option<f64> {
+        match self.steering_angle == 0. {
+            true => None,
+            false => Some(self.clamped_wheelbase() / self.steering_angle.tan().abs()),
+        }
+    }
+
+    /// Wheelbase is public, so it could have been changed to invalid value after construction
+    fn clamped_wheelbase(&self) -> f64 {
+        self.wheelbase.max(WHEELBASE_MIN)
+    }
+}
+
+impl Default for BicycleModel {
+    fn default() -> Self {
+        BicycleModel::new(
+            WHEELBASE_DEFAULT,
+            MAX_STEERING_ANGLE_DEFAULT,
+            STEERING_RATE_DEFAULT,
+        )
+    }
+}
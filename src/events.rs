@@ -0,0 +1,153 @@
+//! Things that happened in the simulation, UI drains them from [`crate::Simulation`] instead of
+//! polling the state every frame
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::{ai::agents::Agents, traffic::Traffic};
+
+/// Events not drained by then are dropped, oldest first, so the queue can't grow without limit
+const MAX_EVENTS: usize = 10_000;
+
+/// What the agent crashed into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CrashCause {
+    Border,
+    /// wrapped value is ID of the traffic car
+    Car(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Event {
+    #[serde(rename_all = "camelCase")]
+    Crashed {
+        tick: u64,
+        agent: usize,
+        cause: CrashCause,
+        x: f64,
+        y: f64,
+    },
+    /// agent got ahead of the traffic car
+    #[serde(rename_all = "camelCase")]
+    Overtook { tick: u64, agent: usize, car: usize },
+    #[serde(rename_all = "camelCase")]
+    NewBest {
+        tick: u64,
+        agent: usize,
+        previous: usize,
+    },
+    #[serde(rename_all = "camelCase")]
+    GenerationEnded {
+        generation: usize,
+        best_fitness: f64,
+        mean_fitness: f64,
+    },
+    /// best agent got past the last vehicle of the scenario, or all agents crashed before that
+    #[serde(rename_all = "camelCase")]
+    ScenarioFinished {
+        tick: u64,
+        name: String,
+        /// agents which did not crash
+        survivors: usize,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EventQueue(VecDeque<Event>);
+
+impl EventQueue {
+    pub fn push(&mut self, event: Event) {
+        if self.0.len() == MAX_EVENTS {
+            self.0.pop_front();
+        }
+        self.0.push_back(event);
+    }
+
+    /// Takes all queued events, oldest first
+    pub fn drain(&mut self) -> Vec<Event> {
+        self.0.drain(..).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// State of the world before a tick, compared with the state after the tick to find out what happened
+#[derive(Debug, Clone)]
+pub struct Observation {
+    best: Option<usize>,
+    /// ID and y coordinate of the agents which were not damaged
+    agents: Vec<(usize, f64)>,
+    traffic: Vec<(usize, f64)>,
+}
+
+impl Observation {
+    pub fn new(agents: &Agents, traffic: &Traffic) -> Self {
+        Observation {
+            best: agents.best_agent().map(|car| car.id),
+            agents: agents
+                .iter()
+                .filter(|car| !car.damaged)
+                .map(|car| (car.id, car.y))
+                .collect(),
+            traffic: traffic.0.iter().map(|car| (car.id, car.y)).collect(),
+        }
+    }
+
+    /// Events of the tick which moved the world from the observed state to the current one
+    pub fn compare(&self, tick: u64, agents: &Agents, traffic: &Traffic) -> Vec<Event> {
+        let mut events = vec![];
+        let traffic_now = traffic
+            .0
+            .iter()
+            .map(|car| (car.id, car.y))
+            .collect::<HashMap<usize, f64>>();
+
+        for (id, y) in self.agents.iter().copied() {
+            let car = match agents.get(id) {
+                Some(car) => car,
+                None => continue,
+            };
+
+            if let Some(cause) = car.crash() {
+                events.push(Event::Crashed {
+                    tick,
+                    agent: id,
+                    cause,
+                    x: car.x(),
+                    y: car.y,
+                });
+                continue;
+            }
+
+            // cars drive towards negative y, so the one with lower y is ahead
+            for (traffic_id, traffic_y) in self.traffic.iter().copied() {
+                match traffic_now.get(&traffic_id) {
+                    Some(now) if y >= traffic_y && car.y < *now => events.push(Event::Overtook {
+                        tick,
+                        agent: id,
+                        car: traffic_id,
+                    }),
+                    _ => (),
+                }
+            }
+        }
+
+        match (self.best, agents.best_agent()) {
+            (Some(previous), Some(best)) if previous != best.id => events.push(Event::NewBest {
+                tick,
+                agent: best.id,
+                previous,
+            }),
+            _ => (),
+        }
+
+        events
+    }
+}
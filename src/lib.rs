@@ -1,19 +1,50 @@
 pub mod ai;
 pub mod car;
 pub mod controls;
+pub mod dynamics;
+pub mod events;
+pub mod pacing;
+pub mod replay;
 pub mod road;
+pub mod save;
+pub mod scenario;
 pub mod sensors;
+pub mod snapshot;
+pub mod storage;
+pub mod telemetry;
 pub mod traffic;
 pub mod utils;
+pub mod vehicle;
 pub mod visualizer;
 
 use std::ops::Deref;
 
-use ai::{agents::Agents, NeuralNetwork};
+use ai::{
+    agents::{self, Agents, HUMAN_AGENT_ID},
+    dataset::Dataset,
+    es::{self, EvolutionStrategy},
+    mutation::{MutationOperator, MutationSchedule, SIGMA_DEFAULT},
+    neat::{Neat, NeatSettings},
+    population::{
+        self, Breeding, GenerationStats, HallOfFame, HallOfFameEntry, NoveltyArchive, Selection,
+    },
+    rl::ReinforceTrainer,
+    training::{Loss, Trainer},
+    NeuralNetwork,
+};
 use car::Car;
+use controls::KeyEvent;
+use events::{Event, EventQueue, Observation};
 use js_sys::Uint32Array;
+use pacing::Pacing;
+use replay::{Replay, ReplayFile};
 use road::Road;
+use save::Save;
+use scenario::{ActiveScenario, Scenario};
+use snapshot::{RewindBuffer, Snapshot, REWIND_INTERVAL};
+use telemetry::{AgentStats, Telemetry};
 use traffic::Traffic;
+use vehicle::TrafficMix;
 use visualizer::Visualizer;
 use wasm_bindgen::prelude::wasm_bindgen;
 use web_sys::CanvasRenderingContext2d;
@@ -41,16 +72,20 @@ macro_rules! error {
 pub const CAR_Y_DEFAULT: f64 = 100.;
 pub const CAR_WIDHT_DEFAULT: f64 = 30.;
 pub const CAR_HEIGHT_DEFAULT: f64 = 50.;
+/// Width of the canvas the road is drawn on, used when simulating without any canvas
+pub const CAR_CANVAS_WIDTH_DEFAULT: f64 = 200.;
 
 const LOCAL_STORAGE_KEY: &str = "bestBrain";
 
 const IDEAL_DISTANCE: f64 = -250.;
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-struct Save {
-    brain: NeuralNetwork,
-    config: Config,
-}
+const HALL_OF_FAME_SIZE_DEFAULT: usize = 10;
+const PLATEAU_FACTOR_DEFAULT: f64 = 0.5;
+
+/// length of the road treated as one state by novelty search
+const NOVELTY_BUCKET: f64 = CAR_HEIGHT_DEFAULT * 2.;
+/// headless training blocks the page, so one call stops starting new generations after this many milliseconds
+const TRAINING_BUDGET: f64 = 1000.;
 
 #[wasm_bindgen]
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -79,6 +114,57 @@ pub struct Config {
     pub hidden_layers: Vec<usize>,
     #[wasm_bindgen(js_name = mutationRate)]
     pub mutation_rate: f64,
+    /// agents steer using kinematic bicycle model instead of constant angle turns
+    #[wasm_bindgen(js_name = bicycleModel)]
+    #[serde(default)]
+    pub bicycle_model: bool,
+    /// probabilities of vehicle types spawned as traffic
+    #[wasm_bindgen(js_name = trafficMix)]
+    #[serde(default)]
+    pub traffic_mix: TrafficMix,
+    /// brains closer than this are of the same species, 0 turns speciation off
+    #[wasm_bindgen(js_name = speciationThreshold)]
+    #[serde(default)]
+    pub speciation_threshold: f64,
+    /// how much is reaching not yet visited states worth compared to the distance travelled, 0 turns novelty search off
+    #[wasm_bindgen(js_name = noveltyWeight)]
+    #[serde(default)]
+    pub novelty_weight: f64,
+    #[serde(default)]
+    pub selection: Selection,
+    /// number of best brains truncation selection picks from, number of brains in a tournament
+    #[wasm_bindgen(js_name = selectionSize)]
+    #[serde(default = "default_one")]
+    pub selection_size: usize,
+    /// number of best brains copied to the next generation unchanged
+    #[serde(default = "default_one")]
+    pub elitism: usize,
+    /// how many best brains across generations are kept in the save
+    #[wasm_bindgen(js_name = hallOfFameSize)]
+    #[serde(default = "default_hall_of_fame_size")]
+    pub hall_of_fame_size: usize,
+    #[wasm_bindgen(js_name = mutationOperator)]
+    #[serde(default)]
+    pub mutation_operator: MutationOperator,
+    /// probability that a weight or bias gets mutated
+    #[wasm_bindgen(js_name = mutationProbability)]
+    #[serde(default = "default_mutation_probability")]
+    pub mutation_probability: f64,
+    /// standard deviation of gaussian mutation
+    #[wasm_bindgen(js_name = mutationSigma)]
+    #[serde(default = "default_mutation_sigma")]
+    pub mutation_sigma: f64,
+    /// mutation gets weaker after this many generations without improvement, 0 turns it off
+    #[wasm_bindgen(js_name = plateauPatience)]
+    #[serde(default)]
+    pub plateau_patience: usize,
+    /// how much weaker the mutation gets on plateau
+    #[wasm_bindgen(js_name = plateauFactor)]
+    #[serde(default = "default_plateau_factor")]
+    pub plateau_factor: f64,
+    /// new brains get recurrent hidden levels, so they remember what they saw in previous ticks
+    #[serde(default)]
+    pub recurrent: bool,
 }
 
 #[wasm_bindgen]
@@ -107,6 +193,20 @@ impl Config {
                 .map(|x| x as usize)
                 .collect(),
             mutation_rate,
+            bicycle_model: false,
+            traffic_mix: TrafficMix::default(),
+            speciation_threshold: 0.,
+            novelty_weight: 0.,
+            selection: Selection::default(),
+            selection_size: 1,
+            elitism: 1,
+            hall_of_fame_size: HALL_OF_FAME_SIZE_DEFAULT,
+            mutation_operator: MutationOperator::default(),
+            mutation_probability: 1.,
+            mutation_sigma: SIGMA_DEFAULT,
+            plateau_patience: 0,
+            plateau_factor: PLATEAU_FACTOR_DEFAULT,
+            recurrent: false,
         }
     }
 
@@ -136,6 +236,26 @@ impl Config {
     }
 }
 
+fn default_one() -> usize {
+    1
+}
+
+fn default_hall_of_fame_size() -> usize {
+    HALL_OF_FAME_SIZE_DEFAULT
+}
+
+fn default_mutation_probability() -> f64 {
+    1.
+}
+
+fn default_mutation_sigma() -> f64 {
+    SIGMA_DEFAULT
+}
+
+fn default_plateau_factor() -> f64 {
+    PLATEAU_FACTOR_DEFAULT
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -147,6 +267,20 @@ impl Default for Config {
             rays_spread: 2.,
             hidden_layers: vec![6],
             mutation_rate: 0.2,
+            bicycle_model: false,
+            traffic_mix: TrafficMix::default(),
+            speciation_threshold: 0.,
+            novelty_weight: 0.,
+            selection: Selection::default(),
+            selection_size: 1,
+            elitism: 1,
+            hall_of_fame_size: HALL_OF_FAME_SIZE_DEFAULT,
+            mutation_operator: MutationOperator::default(),
+            mutation_probability: 1.,
+            mutation_sigma: SIGMA_DEFAULT,
+            plateau_patience: 0,
+            plateau_factor: PLATEAU_FACTOR_DEFAULT,
+            recurrent: false,
         }
     }
 }
@@ -159,6 +293,34 @@ pub struct Simulation {
     agents: Agents,
     road: road::Road,
     config: Config,
+    /// when set, human driver's decisions are stored into `dataset` every tick
+    recording: bool,
+    dataset: Dataset,
+    reinforce: Option<ReinforceTrainer>,
+    /// population of NEAT genomes, when set agents are driven by genomes instead of neural networks
+    neat: Option<Neat>,
+    /// when set, generations are sampled by evolution strategy instead of being bred
+    es: Option<EvolutionStrategy>,
+    generation: usize,
+    /// stats of all finished generations
+    history: Vec<GenerationStats>,
+    novelty: NoveltyArchive,
+    hall_of_fame: HallOfFame,
+    schedule: MutationSchedule,
+    /// number of updates since the simulation was created
+    tick: u64,
+    /// last recorded replay, frames are added every tick while `recording_replay` is set, until it is full
+    replay: Option<Replay>,
+    recording_replay: bool,
+    /// recent states of the world, see [`Simulation::rewind`]
+    rewind: RewindBuffer,
+    pacing: Pacing,
+    events: EventQueue,
+    telemetry: Telemetry,
+    /// when the generation on the road started, see [`utils::now`]
+    generation_started: f64,
+    /// last spawned scenario, until agents finish it
+    scenario: Option<ActiveScenario>,
 }
 
 #[wasm_bindgen]
@@ -171,15 +333,28 @@ impl Simulation {
             config.lanes_count as i32,
         );
 
-        let brain = match window.local_storage().ok().flatten() {
-            Some(storage) => match storage.get_item("bestBrain").ok().flatten() {
+        let save = match window.local_storage().ok().flatten() {
+            Some(storage) => match storage.get_item(LOCAL_STORAGE_KEY).ok().flatten() {
                 Some(raw_save) => {
                     log!("found stored brain");
-                    Some(
-                        serde_json::from_str::<Save>(raw_save.as_str())
-                            .map(|s| s.brain)
-                            .expect("failed to deserialize save data"),
-                    )
+                    match Save::from_json(raw_save.as_str()) {
+                        Ok(save) => {
+                            let migrated = save.to_json();
+                            if migrated != raw_save {
+                                log!("migrating stored brain to the compact format");
+                                if storage.set_item(LOCAL_STORAGE_KEY, &migrated).is_err() {
+                                    error!("failed to store migrated save, old one is kept");
+                                }
+                            }
+
+                            Some(save)
+                        }
+                        // stored save is kept, so it is not lost, but the simulation starts from scratch
+                        Err(e) => {
+                            error!("stored brain can't be loaded, {e}");
+                            None
+                        }
+                    }
                 }
                 _ => None,
             },
@@ -188,11 +363,16 @@ impl Simulation {
 
         let cars = Car::generate_cars_same(
             road.lane_center(config.lane_index as i32),
-            brain.clone(),
+            save.as_ref().map(|s| s.brain.clone()),
             &config,
         );
 
-        Simulation::new(road, Agents::new(cars), Traffic::new(), config.clone())
+        let mut simulation =
+            Simulation::new(road, Agents::new(cars), Traffic::new(), config.clone());
+        if let Some(save) = save {
+            simulation.hall_of_fame = save.hall_of_fame;
+        }
+        simulation
     }
 
     pub fn run(&mut self) {
@@ -230,14 +410,14 @@ impl Simulation {
                 continue;
             }
 
-            self.traffic.add_car(
+            self.traffic.add_mixed_vehicle(
                 self.road.lane_center(lane_index as i32),
                 self.agents
                     .best_agent()
                     .expect("no best agent, can't resolve Y coordinate")
                     .y
                     + (-500. + (i as f64 * IDEAL_DISTANCE)),
-                2.,
+                &self.config.traffic_mix,
             )
         }
     }
@@ -250,33 +430,35 @@ impl Simulation {
         }
 
         for lane_index in lane_indexes.to_vec().into_iter() {
-            self.traffic.add_car(
+            self.traffic.add_mixed_vehicle(
                 self.road.lane_center(lane_index as i32),
                 self.agents
                     .best_agent()
                     .expect("no best agent, can't resolve Y coordinate")
                     .y
                     - 500.,
-                2.,
+                &self.config.traffic_mix,
             )
         }
     }
 
     #[wasm_bindgen(js_name = spawnRandom)]
     pub fn spawn_random(&mut self) {
-        self.traffic.add_car(
+        self.traffic.add_mixed_vehicle(
             self.road.lane_center(
-                (js_sys::Math::random() * (self.config.lanes_count + 1) as f64).floor() as i32,
+                (utils::random() * (self.config.lanes_count + 1) as f64).floor() as i32,
             ),
             self.agents
                 .best_agent()
                 .expect("no best agent, can't resolve Y coordinate")
                 .y
                 - 500.,
-            2.,
+            &self.config.traffic_mix,
         )
     }
 
+    /// Simulates ticks of one animation frame and draws the last one, number of ticks is decided by
+    /// [`Simulation::speed`] or by the time budget when turbo is on
     pub fn step(
         &mut self,
         car_ctx: CanvasRenderingContext2d,
@@ -284,13 +466,74 @@ impl Simulation {
         car_rendering_distance: f64,
         draw_network: bool,
     ) {
-        self.update();
+        let ticks = match self.pacing.turbo_budget() {
+            Some(budget) => self.update_for(budget),
+            None => {
+                let ticks = self.pacing.ticks_for_frame();
+                self.update_n(ticks)
+            }
+        };
+        self.pacing.measure(ticks as u64, utils::now());
+        self.draw(&car_ctx, &network_ctx, car_rendering_distance, draw_network);
+    }
+
+    /// Simulates `ticks` ticks and draws only the last one
+    #[wasm_bindgen(js_name = stepN)]
+    pub fn step_n(
+        &mut self,
+        car_ctx: CanvasRenderingContext2d,
+        network_ctx: CanvasRenderingContext2d,
+        car_rendering_distance: f64,
+        draw_network: bool,
+        ticks: u32,
+    ) {
+        let ticks = self.update_n(ticks);
+        self.pacing.measure(ticks as u64, utils::now());
         self.draw(&car_ctx, &network_ctx, car_rendering_distance, draw_network);
     }
 
+    /// Ticks simulated per frame by [`Simulation::step`], ie. `2` runs twice as fast, `0.5` half as fast
+    #[wasm_bindgen(getter)]
+    pub fn speed(&self) -> f64 {
+        self.pacing.speed()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_speed(&mut self, speed: f64) {
+        self.pacing.set_speed(speed);
+    }
+
+    /// Time budget of a frame in milliseconds, when set [`Simulation::step`] simulates as many ticks
+    /// as fit into it instead of following [`Simulation::speed`]
+    #[wasm_bindgen(getter)]
+    pub fn turbo(&self) -> Option<f64> {
+        self.pacing.turbo_budget()
+    }
+
+    /// Turns turbo on with the given time budget, `undefined` or `0` turns it off
+    #[wasm_bindgen(setter)]
+    pub fn set_turbo(&mut self, budget: Option<f64>) {
+        self.pacing.set_turbo_budget(budget);
+    }
+
+    /// Ticks simulated per second of wall time
+    #[wasm_bindgen(getter, js_name = ticksPerSecond)]
+    pub fn ticks_per_second(&self) -> f64 {
+        self.pacing.ticks_per_second()
+    }
+
     #[wasm_bindgen(js_name = updateConfig)]
     pub fn update_config(&mut self, config: &Config) {
         self.config = config.clone();
+        // schedule keeps how far it lowered the mutation strength, only its settings change
+        self.schedule.patience = config.plateau_patience;
+        self.schedule.factor = config.plateau_factor;
+    }
+
+    /// Changes probabilities of vehicle types used by all traffic spawning methods
+    #[wasm_bindgen(js_name = setTrafficMix)]
+    pub fn set_traffic_mix(&mut self, mix: &TrafficMix) {
+        self.config.traffic_mix = *mix;
     }
 
     #[wasm_bindgen(js_name = top10Agents)]
@@ -310,6 +553,469 @@ impl Simulation {
         self.agents.focus_agent(agent_id);
     }
 
+    /// Values of agent's brain from the last tick, see [`NeuralNetwork::level_activations`]
+    ///
+    /// # Returns
+    /// * `Option<Array>` - `Float64Array` of inputs followed by one for outputs of each level,
+    /// `None` if there is no such agent or it is not driven by a neural network
+    #[wasm_bindgen(js_name = agentActivations)]
+    pub fn agent_activations(&self, agent_id: usize) -> Option<js_sys::Array> {
+        let brain = self.agents.get(agent_id)?.brain()?;
+        Some(
+            brain
+                .level_activations()
+                .into_iter()
+                .map(js_sys::Float64Array::from)
+                .collect(),
+        )
+    }
+
+    /// Telemetry of the agent as JSON object, see [`AgentStats`], agents no longer on the road are included
+    ///
+    /// # Returns
+    /// * `Option<String>` - `None` if there is no such agent in the current generation
+    #[wasm_bindgen(js_name = agentStats)]
+    pub fn agent_stats_json(&self, agent_id: usize) -> Option<String> {
+        self.agent_stats(agent_id)
+            .map(|stats| serde_json::to_string(stats).expect("failed to serialize agent stats"))
+    }
+
+    /// Telemetry of all agents of the current generation as JSON array ordered by agent ID
+    #[wasm_bindgen(js_name = allAgentStats)]
+    pub fn all_agent_stats_json(&self) -> String {
+        serde_json::to_string(&self.telemetry.all()).expect("failed to serialize agent stats")
+    }
+
+    /// Spawns car controlled by keyboard in the starting lane, replaces previous human car if there was any
+    #[wasm_bindgen(js_name = spawnHumanCar)]
+    pub fn spawn_human_car(&mut self) {
+        self.agents.add_human(Car::human(
+            HUMAN_AGENT_ID,
+            self.road.lane_center(self.config.lane_index as i32),
+            &self.config,
+        ));
+    }
+
+    #[wasm_bindgen(js_name = handleKeyInput)]
+    pub fn handle_key_input(&mut self, event: KeyEvent) {
+        self.agents.handle_key_input(event);
+    }
+
+    /// Camera will follow the human driven car
+    #[wasm_bindgen(js_name = focusHuman)]
+    pub fn focus_human(&mut self) {
+        self.agents.focus_agent(HUMAN_AGENT_ID);
+    }
+
+    /// ID under which human driven car shows up in rankings
+    #[wasm_bindgen(js_name = humanAgentId)]
+    pub fn human_agent_id() -> usize {
+        HUMAN_AGENT_ID
+    }
+
+    /// Score of the human driven car, computed the same way as agents' score
+    #[wasm_bindgen(js_name = humanScore)]
+    pub fn human_score(&self) -> Option<f64> {
+        self.agents.human().map(ai::agents::fitness)
+    }
+
+    /// Starts recording human driver's sensor readings and pressed controls
+    #[wasm_bindgen(js_name = startRecording)]
+    pub fn start_recording(&mut self) {
+        self.recording = true;
+    }
+
+    #[wasm_bindgen(js_name = stopRecording)]
+    pub fn stop_recording(&mut self) {
+        self.recording = false;
+    }
+
+    #[wasm_bindgen(getter = isRecording)]
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    #[wasm_bindgen(js_name = recordedSamplesCount)]
+    pub fn recorded_samples_count(&self) -> usize {
+        self.dataset.len()
+    }
+
+    #[wasm_bindgen(js_name = clearRecording)]
+    pub fn clear_recording(&mut self) {
+        self.dataset.clear();
+    }
+
+    #[wasm_bindgen(js_name = exportRecordingJson)]
+    pub fn export_recording_json(&self) -> String {
+        self.dataset.to_json()
+    }
+
+    #[wasm_bindgen(js_name = exportRecordingCsv)]
+    pub fn export_recording_csv(&self) -> String {
+        self.dataset.to_csv()
+    }
+
+    /// Replaces recorded samples with samples from previously exported JSON,
+    /// samples have to match sensor's rays count of the current config
+    #[wasm_bindgen(js_name = importRecordingJson)]
+    pub fn import_recording_json(&mut self, json: &str) -> bool {
+        let dataset = Dataset::from_json(json)
+            .map_err(|err| err.to_string())
+            .and_then(|dataset| dataset.validate(self.config.rays_count, 4).map(|_| dataset));
+
+        match dataset {
+            Ok(dataset) => {
+                self.dataset = dataset;
+                true
+            }
+            Err(err) => {
+                error!("failed to import recording: {err}");
+                false
+            }
+        }
+    }
+
+    /// Trains new brain on the recorded samples and restarts all agents with it,
+    /// first agent gets the trained brain as is, others get its mutated copies
+    ///
+    /// # Returns
+    /// * `f64` - mean loss of the last epoch, `NaN` if the samples do not match the current config
+    #[wasm_bindgen(js_name = trainFromRecording)]
+    pub fn train_from_recording(&mut self, epochs: usize, learning_rate: f64) -> f64 {
+        // recording could have been made before rays count changed
+        if let Err(err) = self.dataset.validate(self.config.rays_count, 4) {
+            error!("can't train from recording: {err}");
+            return f64::NAN;
+        }
+
+        let mut brain = NeuralNetwork::trainable(self.config.neurons_count().deref());
+        // controls are either pressed or not, so we treat outputs as probabilities
+        let loss = Trainer::adam(learning_rate, 32)
+            .with_loss(Loss::CrossEntropy)
+            .train(&mut brain, &self.dataset, epochs)
+            .expect("trainable brain fits the validated recording");
+
+        self.restart_agents(brain);
+
+        loss
+    }
+
+    /// Trains brain with reinforcement learning on the training scenario without drawing anything,
+    /// then restarts all agents with it the same way as [`Simulation::train_from_recording`].
+    /// Repeated calls continue training of the same brain, unless the config changed its shape.
+    ///
+    /// # Returns
+    /// * `f64` - mean fitness of the episodes
+    #[wasm_bindgen(js_name = trainReinforce)]
+    pub fn train_reinforce(&mut self, episodes: usize, learning_rate: f64) -> f64 {
+        // brain of the old shape would not fit sensors of the restarted agents
+        let shape = self.config.neurons_count();
+        if self
+            .reinforce
+            .as_ref()
+            .is_some_and(|trainer| trainer.config().neurons_count() != shape)
+        {
+            self.reinforce = None;
+        }
+
+        let config = &self.config;
+        let trainer = self
+            .reinforce
+            .get_or_insert_with(|| ReinforceTrainer::new(config, learning_rate));
+        trainer.set_learning_rate(learning_rate);
+
+        let fitness = trainer.train(episodes);
+        let brain = trainer.policy();
+        self.restart_agents(brain);
+
+        fitness
+    }
+
+    /// Ends current generation and breeds the next one from brains of agents still on the road, agents
+    /// which fell behind only count in the stats. Brains are split into species when speciation is on
+    /// and the best brain of each species gets children. Every generation starts on empty road.
+    ///
+    /// # Returns
+    /// * `Option<GenerationStats>` - stats of the finished generation, `None` if there were no brains to breed
+    #[wasm_bindgen(js_name = nextGeneration)]
+    pub fn next_generation(&mut self) -> Option<GenerationStats> {
+        if self.neat.is_some() {
+            error!("NEAT population evolves with evolveNeat");
+            return None;
+        }
+
+        let population = self
+            .agents
+            .population()
+            .filter_map(|c| c.brain().map(|b| (c.id, b)))
+            .collect::<Vec<(usize, &NeuralNetwork)>>();
+        if population.is_empty() {
+            error!("no agents with brain to breed");
+            return None;
+        }
+
+        let brains = population.iter().map(|(_, b)| *b).collect::<Vec<_>>();
+        let fitness = population
+            .iter()
+            .map(|(id, _)| self.agents.score(*id).unwrap_or(-CAR_Y_DEFAULT))
+            .collect::<Vec<f64>>();
+        let scores = population
+            .iter()
+            .zip(fitness.iter())
+            .map(|((id, _), f)| f + self.config.novelty_weight * self.novelty.score(*id))
+            .collect::<Vec<f64>>();
+
+        let species = match self.config.speciation_threshold > 0. {
+            true => population::cluster(&brains, self.config.speciation_threshold),
+            false => vec![(0..brains.len()).collect()],
+        };
+
+        // agents which fell behind are not bred, but they are still part of the generation
+        let generation_fitness = fitness
+            .iter()
+            .copied()
+            .chain(self.agents.retired().iter().map(|(_, score)| *score))
+            .collect::<Vec<f64>>();
+
+        let mut breeding = Breeding::from_config(&self.config);
+        let best_fitness = fitness.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        breeding.mutation = breeding.mutation.scaled(self.schedule.update(best_fitness));
+
+        let stats = GenerationStats {
+            diversity: population::diversity(&brains),
+            species: species.len(),
+            novel_states: self.novelty.end_generation(),
+            mutation_scale: self.schedule.scale(),
+            survivors: self.survivors(),
+            wall_time: utils::now() - self.generation_started,
+            ..GenerationStats::from_fitness(self.generation, &generation_fitness)
+        };
+
+        let children = match self.es.as_mut() {
+            // evolution strategy needs fitness of its samples in the order it created them, ie by agent id
+            Some(es) => {
+                let agents = &self.agents;
+                let fitness = (0..es.population())
+                    .map(|id| agents.score(id).unwrap_or(-CAR_Y_DEFAULT))
+                    .collect::<Vec<f64>>();
+                es.tell(&fitness);
+                es.ask()
+            }
+            None => population::breed(
+                &brains,
+                &scores,
+                &species,
+                self.config.cars_count,
+                &breeding,
+            ),
+        };
+
+        let best = (0..brains.len())
+            .max_by(|a, b| fitness[*a].total_cmp(&fitness[*b]))
+            .expect("population is not empty");
+        self.hall_of_fame.submit(
+            HallOfFameEntry {
+                brain: brains[best].clone(),
+                fitness: fitness[best],
+                generation: self.generation,
+            },
+            self.config.hall_of_fame_size,
+        );
+
+        self.events.push(Event::GenerationEnded {
+            generation: stats.generation,
+            best_fitness: stats.best_fitness,
+            mean_fitness: stats.mean_fitness,
+        });
+        self.generation += 1;
+        self.history.push(stats);
+        self.traffic.clear();
+        self.replace_agents(Car::generate_cars(
+            self.road.lane_center(self.config.lane_index as i32),
+            children,
+            &self.config,
+        ));
+
+        Some(stats)
+    }
+
+    /// Replaces agents with samples of evolution strategy started from the best agent's brain,
+    /// [`Simulation::next_generation`] then updates the strategy instead of breeding
+    #[wasm_bindgen(js_name = startEvolutionStrategy)]
+    pub fn start_evolution_strategy(&mut self, sigma: f64, learning_rate: f64) {
+        let start = self
+            .agents
+            .best_agent()
+            .and_then(|a| a.brain().cloned())
+            .unwrap_or_else(|| NeuralNetwork::from_config(&self.config));
+
+        let mut es = EvolutionStrategy::new(&start, sigma, learning_rate, self.config.cars_count);
+        let samples = es.ask();
+        self.es = Some(es);
+        self.neat = None;
+        self.replace_agents(Car::generate_cars(
+            self.road.lane_center(self.config.lane_index as i32),
+            samples,
+            &self.config,
+        ));
+    }
+
+    /// Runs evolution strategy on the training scenario without drawing anything, then shows its samples.
+    /// Strategy is started from the best agent's brain if it is not running yet.
+    ///
+    /// Training stops early when it takes longer than [`TRAINING_BUDGET`], so the page stays responsive,
+    /// more generations are trained by calling it again.
+    ///
+    /// # Returns
+    /// * `f64` - fitness of the strategy's mean brain after the last generation
+    #[wasm_bindgen(js_name = trainEvolutionStrategy)]
+    pub fn train_evolution_strategy(&mut self, generations: usize) -> f64 {
+        if self.es.is_none() {
+            self.start_evolution_strategy(es::SIGMA_DEFAULT, es::LEARNING_RATE_DEFAULT);
+        }
+
+        let config = &self.config;
+        let Some(es) = self.es.as_mut() else {
+            return 0.;
+        };
+
+        let started = utils::now();
+        let mut fitness = 0.;
+        // samples on the road were not evaluated, every generation asks for new ones
+        for _ in 0..generations {
+            fitness = es.train_generation(config);
+            if utils::now() - started > TRAINING_BUDGET {
+                break;
+            }
+        }
+
+        let samples = es.ask();
+        self.replace_agents(Car::generate_cars(
+            self.road.lane_center(self.config.lane_index as i32),
+            samples,
+            &self.config,
+        ));
+
+        fitness
+    }
+
+    /// Number of the generation currently on the road, first one is 0
+    #[wasm_bindgen(getter)]
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    #[wasm_bindgen(js_name = lastGenerationStats)]
+    pub fn last_generation_stats(&self) -> Option<GenerationStats> {
+        self.history.last().copied()
+    }
+
+    /// Stats of all finished generations as CSV, see [`GenerationStats::CSV_HEADER`] for the columns
+    #[wasm_bindgen(js_name = historyCsv)]
+    pub fn history_csv(&self) -> String {
+        GenerationStats::to_csv(&self.history)
+    }
+
+    /// Stats of all finished generations as JSON array
+    #[wasm_bindgen(js_name = historyJson)]
+    pub fn history_json(&self) -> String {
+        serde_json::to_string(&self.history).expect("failed to serialize history")
+    }
+
+    /// Draws best, mean, median and worst fitness of the finished generations as line chart
+    #[wasm_bindgen(js_name = drawFitnessChart)]
+    pub fn draw_fitness_chart(&self, ctx: &CanvasRenderingContext2d) {
+        Visualizer::draw_fitness_chart(ctx, &self.history);
+    }
+
+    /// Number of brains in the hall of fame
+    #[wasm_bindgen(js_name = hallOfFameSize)]
+    pub fn hall_of_fame_size(&self) -> usize {
+        self.hall_of_fame.entries.len()
+    }
+
+    /// Restarts all agents from brain in the hall of fame, 0 is the best one
+    ///
+    /// # Returns
+    /// * `bool` - `false` if there is no such brain
+    #[wasm_bindgen(js_name = restartFromHallOfFame)]
+    pub fn restart_from_hall_of_fame(&mut self, index: usize) -> bool {
+        match self.hall_of_fame.entries.get(index) {
+            Some(entry) => {
+                let brain = entry.brain.clone();
+                self.restart_agents(brain);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces agents with cars driven by NEAT genomes, genomes start with sensor rays connected directly
+    /// to controls and grow new neurons and connections as they evolve
+    #[wasm_bindgen(js_name = startNeat)]
+    pub fn start_neat(&mut self) {
+        let settings = NeatSettings {
+            mutation_rate: self.config.mutation_rate,
+            ..NeatSettings::default()
+        };
+        self.es = None;
+        self.neat = Some(Neat::new(
+            self.config.rays_count,
+            4,
+            self.config.cars_count,
+            settings,
+        ));
+        self.restart_neat_agents();
+    }
+
+    /// Breeds next generation of genomes from scores of the current agents and restarts them
+    ///
+    /// # Returns
+    /// * `usize` - number of the new generation
+    #[wasm_bindgen(js_name = evolveNeat)]
+    pub fn evolve_neat(&mut self) -> usize {
+        let agents = &self.agents;
+        let neat = match self.neat.as_mut() {
+            Some(neat) => neat,
+            None => {
+                error!("NEAT is not running, call startNeat first");
+                return 0;
+            }
+        };
+
+        let fitness = (0..neat.genomes.len())
+            .map(|id| agents.score(id).unwrap_or(-CAR_Y_DEFAULT))
+            .collect::<Vec<f64>>();
+        let stats = GenerationStats {
+            species: neat.species.len(),
+            mutation_scale: 1.,
+            survivors: agents.population().filter(|c| !c.damaged).count(),
+            wall_time: utils::now() - self.generation_started,
+            ..GenerationStats::from_fitness(neat.generation, &fitness)
+        };
+        neat.evolve(&fitness);
+        let generation = neat.generation;
+
+        self.events.push(Event::GenerationEnded {
+            generation: stats.generation,
+            best_fitness: stats.best_fitness,
+            mean_fitness: stats.mean_fitness,
+        });
+        self.history.push(stats);
+
+        self.restart_neat_agents();
+        generation
+    }
+
+    /// Number of species in the NEAT population, 0 when NEAT is not running
+    #[wasm_bindgen(js_name = neatSpeciesCount)]
+    pub fn neat_species_count(&self) -> usize {
+        self.neat
+            .as_ref()
+            .map(|n| n.species.len())
+            .unwrap_or_default()
+    }
+
     #[wasm_bindgen(js_name = resetFocus)]
     pub fn reset_focus(&mut self) {
         self.agents.focus_best_agent();
@@ -317,235 +1023,40 @@ impl Simulation {
 
     #[wasm_bindgen(js_name = addTestTraffic)]
     pub fn add_basic_traffic(&mut self, distance_ratio: f64) {
-        // | |x|x|
-        // | | | |
-        // |x| |x|
-        // | | | |
-        // |x|x| |
-        // | | | |
-        // |x| |X|
-        // | | | |
-        // | |x| |
-        // | | | |
-        // |x| |x|
-
-        enum Test {
-            One(i32),
-            Two((i32, i32)),
-        }
-
-        let tests = vec![
-            Test::Two((0, 2)),
-            Test::Two((0, 2)),
-            Test::One(1),
-            Test::Two((0, 2)),
-            Test::Two((0, 1)),
-            Test::Two((0, 2)),
-            Test::Two((1, 2)),
-        ];
-
         let y = self
             .agents
             .best_agent()
             .expect("no best agent, can't resolve Y coordinate")
             .y;
 
-        for (index, test) in tests.into_iter().enumerate() {
-            match test {
-                Test::One(lane) => {
-                    self.traffic.add(Car::no_control(
-                        self.road.lane_center(lane),
-                        y + ((index + 1) as f64 * IDEAL_DISTANCE * distance_ratio),
-                        2.,
-                    ));
-                }
-                Test::Two((lane_1, lane_2)) => {
-                    self.traffic.add(Car::no_control(
-                        self.road.lane_center(lane_1),
-                        y + ((index + 1) as f64 * IDEAL_DISTANCE * distance_ratio),
-                        2.,
-                    ));
-                    self.traffic.add(Car::no_control(
-                        self.road.lane_center(lane_2),
-                        y + ((index + 1) as f64 * IDEAL_DISTANCE * distance_ratio),
-                        2.,
-                    ));
-                }
-            }
-        }
+        self.spawn_scenario(
+            Scenario::test(distance_ratio).with_mix(self.config.traffic_mix),
+            y,
+        );
     }
 
     #[wasm_bindgen(js_name = trainingTraffic)]
     pub fn training_traffic(&mut self) {
-        const DISTANCE: f64 = 250.;
-
-        let mut y = self
+        let y = self
             .agents
             .best_agent()
             .expect("no best agent, can't resolve Y coordinate")
             .y
             .abs();
 
-        y = y + 150.;
-
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(2), -y, 2.));
-        }
-
-        y = y + DISTANCE / 3.;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(2), -y, 2.));
-        }
-
-        // spread, avoid car in the middle, ie can turn left or right
-        y = y + DISTANCE;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-        }
-
-        y = y + DISTANCE / 3.;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-        }
-
-        // from spreading return to middle
-        y = y + DISTANCE;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(2), -y, 2.));
-        }
-
-        y = y + DISTANCE / 3.;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(2), -y, 2.));
-        }
-
-        // turn to right most lane
-        y = y + DISTANCE;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-        }
-
-        y = y + DISTANCE / 3.;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-        }
-
-        // spread
-        y = y + DISTANCE;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-        }
-
-        y = y + DISTANCE / 3.;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-        }
-
-        // return to middle
-        y = y + DISTANCE;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(2), -y, 2.));
-        }
-
-        y = y + DISTANCE / 3.;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(2), -y, 2.));
-        }
-
-        // turn to left most lane
-        y = y + DISTANCE;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(2), -y, 2.));
-        }
-
-        y = y + DISTANCE / 3.;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(2), -y, 2.));
-        }
-
-        // turn to right most lane
-        y = y + DISTANCE;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-        }
-
-        y = y + DISTANCE / 3.;
-        for _ in 0..3 {
-            y = y + CAR_HEIGHT_DEFAULT + 10.;
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(0), -y, 2.));
-            self.traffic
-                .add(Car::no_control(self.road.lane_center(1), -y, 2.));
-        }
+        self.spawn_scenario(Scenario::training().with_mix(self.config.traffic_mix), -y);
     }
 
     #[wasm_bindgen(js_name = saveFocusedCar)]
     pub fn save_best_focused_car(&self, window: &web_sys::Window) {
-        let save = Save {
-            brain: self
-                .agents
-                .focused_agent()
-                .expect("no best agent found")
-                .brain()
-                .expect("agent without brain")
-                .clone(),
-            config: self.config.clone(),
+        let serialized_data = match self.focused_save() {
+            Some(save) => save.to_json(),
+            None => {
+                error!("focused agent has no brain to save, is it driven by human?");
+                return;
+            }
         };
 
-        let serialized_data = serde_json::to_string(&save).expect("failed to serialize save data");
-
         window
             .local_storage()
             .ok()
@@ -555,6 +1066,90 @@ impl Simulation {
             .expect("failed to save brain to local storage");
     }
 
+    /// Save of the focused agent as JSON, same as the one stored by [`Simulation::save_best_focused_car`],
+    /// so it can be shared as a file
+    #[wasm_bindgen(js_name = exportSave)]
+    pub fn export_save(&self) -> Option<String> {
+        self.focused_save().map(|save| save.to_json())
+    }
+
+    /// Same as [`Simulation::export_save`], but wrapped in JSON `Blob` ready to be downloaded
+    #[wasm_bindgen(js_name = exportSaveBlob)]
+    pub fn export_save_blob(&self) -> Option<web_sys::Blob> {
+        json_blob(self.export_save()?)
+    }
+
+    /// Starts recording a new replay, the previous one is discarded
+    ///
+    /// Recording stops by itself once the replay has [`replay::MAX_REPLAY_FRAMES`] frames
+    #[wasm_bindgen(js_name = startReplay)]
+    pub fn start_replay(&mut self) {
+        let mut replay = Replay::new(self.road.clone());
+        replay.record(&self.traffic, &self.agents);
+        self.replay = Some(replay);
+        self.recording_replay = true;
+    }
+
+    /// Stops recording the replay, it is kept until the next one is started
+    #[wasm_bindgen(js_name = stopReplay)]
+    pub fn stop_replay(&mut self) {
+        self.recording_replay = false;
+    }
+
+    #[wasm_bindgen(getter, js_name = isRecordingReplay)]
+    pub fn is_recording_replay(&self) -> bool {
+        self.recording_replay
+    }
+
+    #[wasm_bindgen(getter, js_name = replayFramesCount)]
+    pub fn replay_frames_count(&self) -> usize {
+        self.replay
+            .as_ref()
+            .map(|r| r.frames.len())
+            .unwrap_or_default()
+    }
+
+    /// Recorded replay as JSON together with the save of the focused agent, it can be played by `ReplayPlayer`
+    #[wasm_bindgen(js_name = exportReplay)]
+    pub fn export_replay(&self) -> Option<String> {
+        let file = ReplayFile {
+            save: self.focused_save(),
+            replay: self.replay.clone()?,
+        };
+        Some(file.to_json())
+    }
+
+    /// Same as [`Simulation::export_replay`], but wrapped in JSON `Blob` ready to be downloaded
+    #[wasm_bindgen(js_name = exportReplayBlob)]
+    pub fn export_replay_blob(&self) -> Option<web_sys::Blob> {
+        json_blob(self.export_replay()?)
+    }
+
+    /// Validates save exported by [`Simulation::export_save`] and stores it in local storage in place of
+    /// the saved brain, simulation created afterwards starts from it
+    ///
+    /// # Returns
+    /// * `bool` - `false` if the save is not valid, the reason is logged
+    #[wasm_bindgen(js_name = importSave)]
+    pub fn import_save(window: &web_sys::Window, text: &str) -> bool {
+        let save = match Save::from_json(text) {
+            Ok(save) => save,
+            Err(e) => {
+                error!("failed to import save, {e}");
+                return false;
+            }
+        };
+
+        window
+            .local_storage()
+            .ok()
+            .flatten()
+            .expect("failed to get local storage")
+            .set_item(LOCAL_STORAGE_KEY, save.to_json().as_str())
+            .expect("failed to save brain to local storage");
+        true
+    }
+
     pub fn discard_brain(window: &web_sys::Window) {
         window
             .local_storage()
@@ -582,6 +1177,93 @@ impl Simulation {
         }
     }
 
+    /// Number of updates since the simulation was created
+    #[wasm_bindgen(getter)]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Captures the world as JSON, see [`Simulation::capture_snapshot`]
+    pub fn snapshot(&self) -> String {
+        self.capture_snapshot().to_json()
+    }
+
+    /// Restores the world from JSON created by [`Simulation::snapshot`]
+    ///
+    /// # Returns
+    /// * `bool` - `false` if the snapshot is not valid, the reason is logged
+    pub fn restore(&mut self, json: &str) -> bool {
+        match Snapshot::from_json(json) {
+            Ok(snapshot) => {
+                self.restore_snapshot(snapshot);
+                true
+            }
+            Err(e) => {
+                error!("failed to restore snapshot, {e}");
+                false
+            }
+        }
+    }
+
+    /// Events which happened since the last call as JSON array, oldest first. Every event has `type`
+    /// field, one of `crashed`, `overtook`, `newBest`, `generationEnded` or `scenarioFinished`.
+    #[wasm_bindgen(js_name = drainEvents)]
+    pub fn drain_events_json(&mut self) -> String {
+        serde_json::to_string(&self.drain_events()).expect("failed to serialize events")
+    }
+
+    /// Rewinds the world by `ticks`, at most to the oldest stored state. Simulation is paused,
+    /// so the rewound moment can be watched tick by tick with [`Simulation::step_forward`].
+    ///
+    /// Ticks after the closest stored state are simulated again, so changes made by the user in the
+    /// meantime, like spawned traffic or keys pressed by the human driver, are not repeated.
+    ///
+    /// # Returns
+    /// * `u32` - number of ticks the world was actually rewound by
+    pub fn rewind(&mut self, ticks: u32) -> u32 {
+        if matches!(self.state, SimulationState::Stopped) {
+            return 0;
+        }
+
+        let oldest = self.rewind.oldest_tick().unwrap_or(self.tick);
+        let target = self.tick.saturating_sub(ticks as u64).max(oldest);
+        let snapshot = match self.rewind.latest_before(target) {
+            Some(snapshot) if target < self.tick => snapshot.clone(),
+            _ => return 0,
+        };
+
+        let rewound = self.tick - target;
+        self.pause();
+        self.restore_world(snapshot);
+        while self.tick < target {
+            self.simulate();
+        }
+        rewound as u32
+    }
+
+    /// Goes one tick back, works only while the simulation is paused
+    #[wasm_bindgen(js_name = stepBack)]
+    pub fn step_back(&mut self) -> bool {
+        matches!(self.state, SimulationState::Paused) && self.rewind(1) == 1
+    }
+
+    /// Goes one tick forward, works only while the simulation is paused
+    #[wasm_bindgen(js_name = stepForward)]
+    pub fn step_forward(&mut self) -> bool {
+        if !matches!(self.state, SimulationState::Paused) {
+            return false;
+        }
+
+        self.advance();
+        true
+    }
+
+    /// How many ticks can the world be rewound by
+    #[wasm_bindgen(getter, js_name = rewindableTicks)]
+    pub fn rewindable_ticks(&self) -> u64 {
+        self.tick - self.rewind.oldest_tick().unwrap_or(self.tick)
+    }
+
     #[wasm_bindgen(js_name = getFocusedAgentY)]
     pub fn focus_agent_y(&self) -> f64 {
         self.agents.best_agent().map(|c| c.y).unwrap_or_default()
@@ -589,20 +1271,263 @@ impl Simulation {
 }
 
 impl Simulation {
+    fn focused_save(&self) -> Option<Save> {
+        let brain = self.agents.focused_agent()?.brain()?.clone();
+        Some(Save {
+            brain,
+            config: self.config.clone(),
+            hall_of_fame: self.hall_of_fame.clone(),
+        })
+    }
+
     fn new(road: Road, agents: Agents, traffic: Traffic, config: Config) -> Self {
+        let schedule = MutationSchedule::new(config.plateau_patience, config.plateau_factor);
+        let telemetry = Telemetry::new(&agents, &road);
         Simulation {
             state: SimulationState::Stopped,
             traffic,
             agents,
             road,
             config,
+            recording: false,
+            dataset: Dataset::new(),
+            reinforce: None,
+            neat: None,
+            es: None,
+            generation: 0,
+            history: vec![],
+            novelty: NoveltyArchive::new(NOVELTY_BUCKET),
+            hall_of_fame: HallOfFame::default(),
+            schedule,
+            tick: 0,
+            replay: None,
+            recording_replay: false,
+            rewind: RewindBuffer::default(),
+            pacing: Pacing::default(),
+            events: EventQueue::default(),
+            telemetry,
+            generation_started: utils::now(),
+            scenario: None,
         }
     }
 
-    fn update(&mut self) {
+    /// Creates simulation without browser, agents start with new brains and there is no traffic
+    pub fn headless(config: &Config) -> Self {
+        let road = Road::new(
+            CAR_CANVAS_WIDTH_DEFAULT / 2.,
+            CAR_CANVAS_WIDTH_DEFAULT * 0.9,
+            config.lanes_count as i32,
+        );
+        let cars =
+            Car::generate_cars_same(road.lane_center(config.lane_index as i32), None, config);
+        Simulation::new(road, Agents::new(cars), Traffic::new(), config.clone())
+    }
+
+    /// Captures the world, restoring the snapshot with [`Simulation::restore_snapshot`] continues exactly from this moment
+    pub fn capture_snapshot(&self) -> Snapshot {
+        Snapshot {
+            tick: self.tick,
+            rng_state: utils::random_state(),
+            config: self.config.clone(),
+            road: self.road.clone(),
+            traffic: self.traffic.clone(),
+            agents: self.agents.clone(),
+            telemetry: self.telemetry.clone(),
+        }
+    }
+
+    /// Replaces the world by the captured one, state of training algorithms and generations is kept
+    pub fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        // stored states belong to the replaced world
+        self.rewind.clear();
+        self.restore_world(snapshot);
+    }
+
+    fn restore_world(&mut self, snapshot: Snapshot) {
+        self.tick = snapshot.tick;
+        // zero means the generator was not used yet
+        if snapshot.rng_state != 0 {
+            utils::seed_random(snapshot.rng_state);
+        }
+        self.config = snapshot.config;
+        self.road = snapshot.road;
+        self.traffic = snapshot.traffic;
+        self.agents = snapshot.agents;
+        self.telemetry = snapshot.telemetry;
+    }
+
+    /// Replaces all agents with new ones starting from the beginning, first agent gets `brain` as is,
+    /// others get its mutated copies. Human driver, if there was one, starts again too.
+    fn restart_agents(&mut self, brain: NeuralNetwork) {
+        self.neat = None;
+        self.es = None;
+        self.replace_agents(Car::generate_cars_same(
+            self.road.lane_center(self.config.lane_index as i32),
+            Some(brain),
+            &self.config,
+        ));
+    }
+
+    /// Replaces all AI agents, human driver, if there was one, starts again too
+    fn replace_agents(&mut self, cars: Vec<Car>) {
+        let human = self.agents.human().is_some();
+        self.agents = Agents::new(cars);
+        self.telemetry = Telemetry::new(&self.agents, &self.road);
+        self.generation_started = utils::now();
+        self.rewind.clear();
+        if human {
+            self.spawn_human_car();
+        }
+    }
+
+    /// Replaces all agents with cars driven by genomes of the NEAT population, agent's id is index of its genome
+    fn restart_neat_agents(&mut self) {
+        let neat = match self.neat.as_ref() {
+            Some(neat) => neat,
+            None => return,
+        };
+
+        let lane = self.road.lane_center(self.config.lane_index as i32);
+        let cars = neat
+            .genomes
+            .iter()
+            .enumerate()
+            .map(|(id, genome)| Car::with_genome(id, lane, genome.clone(), &self.config))
+            .collect();
+        self.replace_agents(cars);
+    }
+
+    /// Advances the world by one tick without drawing it, does nothing unless the simulation is running
+    pub fn update(&mut self) {
         if !matches!(self.state, SimulationState::Running) {
             return;
         }
+        self.advance();
+    }
+
+    /// Advances the world by one tick no matter the state, the world is captured for rewinding and
+    /// recordings are updated
+    fn advance(&mut self) {
+        if self.tick.is_multiple_of(REWIND_INTERVAL) {
+            self.rewind.push(self.capture_snapshot());
+        }
+        for event in self.simulate() {
+            self.events.push(event);
+        }
+        self.check_scenario();
+
+        if self.config.novelty_weight > 0. {
+            for car in self.agents.iter().filter(|c| !c.damaged && !c.is_human()) {
+                self.novelty
+                    .visit(car.id, self.road.lane_index(car.x()), agents::fitness(car));
+            }
+        }
+
+        if self.recording {
+            self.record_human();
+        }
+
+        match self.replay.as_mut() {
+            // replay which is full is kept as it is
+            Some(replay) if self.recording_replay => {
+                self.recording_replay = replay.record(&self.traffic, &self.agents)
+            }
+            _ => (),
+        }
+    }
+
+    /// Advances the world by up to `ticks` ticks, stops early when the simulation stops running
+    ///
+    /// # Returns
+    /// * `u32` - number of ticks actually simulated
+    pub fn update_n(&mut self, ticks: u32) -> u32 {
+        let mut simulated = 0;
+        while simulated < ticks && matches!(self.state, SimulationState::Running) {
+            self.advance();
+            simulated += 1;
+        }
+        simulated
+    }
+
+    /// Advances the world until `budget` milliseconds pass, at least one tick is simulated while running
+    ///
+    /// # Returns
+    /// * `u32` - number of ticks actually simulated
+    pub fn update_for(&mut self, budget: f64) -> u32 {
+        let start = utils::now();
+        let mut simulated = 0;
+        while matches!(self.state, SimulationState::Running)
+            && (simulated == 0 || utils::now() - start < budget)
+        {
+            self.advance();
+            simulated += 1;
+        }
+        simulated
+    }
+
+    fn spawn_scenario(&mut self, scenario: Scenario, start_y: f64) {
+        scenario.spawn(&self.road, &mut self.traffic, start_y);
+        self.scenario = Some(ActiveScenario {
+            finish_y: scenario.finish_y(start_y),
+            name: scenario.name,
+        });
+    }
+
+    /// Scenario is finished once the best agent gets past its last vehicle or when all agents crash
+    fn check_scenario(&mut self) {
+        let finish_y = match self.scenario.as_ref() {
+            Some(scenario) => scenario.finish_y,
+            None => return,
+        };
+
+        let passed = self.agents.best_agent().is_some_and(|car| car.y < finish_y);
+        if !passed && self.agents.iter().any(|car| !car.damaged) {
+            return;
+        }
+
+        let Some(scenario) = self.scenario.take() else {
+            return;
+        };
+        self.events.push(Event::ScenarioFinished {
+            tick: self.tick,
+            name: scenario.name,
+            survivors: self.agents.iter().filter(|car| !car.damaged).count(),
+        });
+    }
+
+    /// Stats of all finished generations, oldest first
+    pub fn history(&self) -> &[GenerationStats] {
+        &self.history
+    }
+
+    /// AI agents of the generation which did not crash
+    fn survivors(&self) -> usize {
+        self.agents
+            .population()
+            .filter(|c| !c.damaged && c.brain().is_some())
+            .count()
+    }
+
+    pub fn agent_stats(&self, agent_id: usize) -> Option<&AgentStats> {
+        self.telemetry.get(agent_id)
+    }
+
+    pub fn telemetry(&self) -> &Telemetry {
+        &self.telemetry
+    }
+
+    /// Takes all events which happened since the last call, oldest first
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        self.events.drain()
+    }
+
+    /// Moves the world by one tick, nothing is captured nor recorded, so already seen ticks can be simulated again
+    ///
+    /// # Returns
+    /// * `Vec<Event>` - events of the tick, telemetry is already updated from them
+    fn simulate(&mut self) -> Vec<Event> {
+        let observation = Observation::new(&self.agents, &self.traffic);
+        self.tick += 1;
 
         if let Some(a) = self.agents.best_agent() {
             self.traffic.clean(a.y);
@@ -612,6 +1537,23 @@ impl Simulation {
         self.traffic.update();
         self.agents.clean();
         self.agents.update(&self.road, &self.traffic);
+
+        let events = observation.compare(self.tick, &self.agents, &self.traffic);
+        self.telemetry
+            .record(&self.agents, &self.road, &self.traffic, &events);
+        events
+    }
+
+    /// Stores what human driver sees and what controls are pressed as a training sample
+    fn record_human(&mut self) {
+        let human = match self.agents.human() {
+            Some(human) if !human.damaged => human,
+            _ => return,
+        };
+
+        if let Some(inputs) = human.sensor_inputs() {
+            self.dataset.push(inputs, human.controls().as_outputs());
+        }
     }
 
     fn draw(
@@ -633,15 +1575,18 @@ impl Simulation {
             }
         };
 
-        if draw_network {
-            // draw best cars neural network
-            network_ctx.set_line_dash_offset(focused_agent.y / 5.);
-            Visualizer::draw_network(
-                &network_ctx,
-                focused_agent
-                    .brain()
-                    .expect("best agent doesn't have brain"),
-            );
+        // human driven car has no brain to draw
+        match (focused_agent.brain(), focused_agent.genome()) {
+            (Some(brain), _) if draw_network => {
+                // draw best cars neural network
+                network_ctx.set_line_dash_offset(focused_agent.y / 5.);
+                Visualizer::draw_network(network_ctx, brain);
+            }
+            (None, Some(genome)) if draw_network => {
+                network_ctx.set_line_dash_offset(focused_agent.y / 5.);
+                Visualizer::draw_genome(network_ctx, genome);
+            }
+            _ => (),
         }
 
         // save context
@@ -662,3 +1607,13 @@ impl Simulation {
         car_ctx.restore();
     }
 }
+
+fn json_blob(json: String) -> Option<web_sys::Blob> {
+    let parts = js_sys::Array::of1(&json.into());
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type("application/json");
+
+    web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)
+        .map_err(|e| error!("failed to create blob: {e:?}"))
+        .ok()
+}
@@ -0,0 +1,75 @@
+//! How many ticks are simulated per animation frame and how fast the simulation actually runs
+
+/// Length of the window ticks per second are averaged over, in milliseconds
+const MEASURE_WINDOW: f64 = 1000.;
+
+#[derive(Debug, Clone)]
+pub struct Pacing {
+    /// ticks simulated per frame, fractions are carried over to the next frames, so `0.5` simulates every other frame
+    speed: f64,
+    carry: f64,
+    /// when set, every frame simulates as many ticks as fit into this many milliseconds and `speed` is ignored
+    turbo_budget: Option<f64>,
+    window_start: Option<f64>,
+    window_ticks: u64,
+    ticks_per_second: f64,
+}
+
+impl Default for Pacing {
+    fn default() -> Self {
+        Pacing {
+            speed: 1.,
+            carry: 0.,
+            turbo_budget: None,
+            window_start: None,
+            window_ticks: 0,
+            ticks_per_second: 0.,
+        }
+    }
+}
+
+impl Pacing {
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.max(0.);
+        self.carry = 0.;
+    }
+
+    pub fn turbo_budget(&self) -> Option<f64> {
+        self.turbo_budget
+    }
+
+    /// Sets time budget of a frame in milliseconds, `None` or non-positive budget turns turbo off
+    pub fn set_turbo_budget(&mut self, budget: Option<f64>) {
+        self.turbo_budget = budget.filter(|budget| *budget > 0.);
+    }
+
+    /// Number of ticks the next frame should simulate according to `speed`
+    pub fn ticks_for_frame(&mut self) -> u32 {
+        self.carry += self.speed;
+        let ticks = self.carry.floor();
+        self.carry -= ticks;
+        ticks as u32
+    }
+
+    /// Counts ticks simulated by the frame which ended at `now`, ticks per second are updated once per second
+    pub fn measure(&mut self, ticks: u64, now: f64) {
+        let start = *self.window_start.get_or_insert(now);
+        self.window_ticks += ticks;
+
+        let elapsed = now - start;
+        if elapsed >= MEASURE_WINDOW {
+            self.ticks_per_second = self.window_ticks as f64 * 1000. / elapsed;
+            self.window_start = Some(now);
+            self.window_ticks = 0;
+        }
+    }
+
+    /// Ticks simulated per second of wall time, averaged over the last finished second
+    pub fn ticks_per_second(&self) -> f64 {
+        self.ticks_per_second
+    }
+}
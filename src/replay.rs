@@ -0,0 +1,451 @@
+//! Recording of a run as poses and controls of every car in every tick
+//!
+//! Replay is played back by drawing the recorded poses, physics and brains are not run again,
+//! so it looks the same no matter which version of the simulation plays it.
+//!
+//! Frames are stored as base64 encoded binary, all numbers are little endian:
+//! * header: magic `SDCR`, format version (`u8`), number of frames (`u32`)
+//! * each frame: ID of the focused agent (`u32`), number of cars (`u32`)
+//! * each car: ID (`u32`), kind (`u8`), flags (`u8`, see [`CarPose::flags`]), x, y and angle (`f32` each)
+
+use wasm_bindgen::prelude::*;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{
+    ai::{agents::Agents, format::Reader},
+    car::Car,
+    error,
+    road::Road,
+    save::Save,
+    traffic::Traffic,
+    vehicle::VehicleType,
+};
+
+const MAGIC: &[u8; 4] = b"SDCR";
+/// Version of the binary layout, bump it whenever the layout changes and keep decoding the old ones
+pub const REPLAY_VERSION: u8 = 1;
+/// Size of one encoded [`CarPose`]
+const POSE_SIZE: usize = 18;
+/// Longest replay which is recorded, 5 minutes at 60 ticks per second, so long recording can't eat all memory
+pub const MAX_REPLAY_FRAMES: usize = 5 * 60 * 60;
+
+const UP: u8 = 1;
+const LEFT: u8 = 1 << 1;
+const RIGHT: u8 = 1 << 2;
+const DOWN: u8 = 1 << 3;
+const DAMAGED: u8 = 1 << 4;
+
+/// Decides how the recorded car is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarKind {
+    Agent,
+    Human,
+    Traffic(VehicleType),
+}
+
+impl CarKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            CarKind::Agent => 0,
+            CarKind::Human => 1,
+            CarKind::Traffic(vehicle_type) => {
+                2 + VehicleType::ALL
+                    .iter()
+                    .position(|t| *t == vehicle_type)
+                    .unwrap_or_default() as u8
+            }
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, String> {
+        match byte {
+            0 => Ok(CarKind::Agent),
+            1 => Ok(CarKind::Human),
+            _ => VehicleType::ALL
+                .get(byte as usize - 2)
+                .map(|t| CarKind::Traffic(*t))
+                .ok_or_else(|| format!("unknown car kind '{byte}'")),
+        }
+    }
+}
+
+/// Position and controls of a single car in a single tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CarPose {
+    pub id: u32,
+    pub kind: CarKind,
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+    /// pressed controls and damage, bit 0 is up, 1 left, 2 right, 3 down and 4 is set for damaged cars
+    pub flags: u8,
+}
+
+impl CarPose {
+    pub fn new(
+        id: u32,
+        kind: CarKind,
+        (x, y, angle): (f64, f64, f64),
+        [up, left, right, down]: [bool; 4],
+        damaged: bool,
+    ) -> Self {
+        let flags = [
+            (up, UP),
+            (left, LEFT),
+            (right, RIGHT),
+            (down, DOWN),
+            (damaged, DAMAGED),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, bit)| flags | bit);
+
+        CarPose {
+            id,
+            kind,
+            x: x as f32,
+            y: y as f32,
+            angle: angle as f32,
+            flags,
+        }
+    }
+
+    /// Pressed controls as `[up, left, right, down]`
+    pub fn controls(&self) -> [bool; 4] {
+        [UP, LEFT, RIGHT, DOWN].map(|bit| self.flags & bit != 0)
+    }
+
+    pub fn damaged(&self) -> bool {
+        self.flags & DAMAGED != 0
+    }
+}
+
+/// All cars on the road in a single tick
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// ID of the agent the camera follows
+    pub focused: u32,
+    pub cars: Vec<CarPose>,
+}
+
+impl Frame {
+    pub fn capture(traffic: &Traffic, agents: &Agents) -> Self {
+        Frame {
+            focused: agents
+                .focused_agent()
+                .map(|car| car.id as u32)
+                .unwrap_or_default(),
+            cars: traffic
+                .0
+                .iter()
+                .chain(agents.iter())
+                .map(Car::pose)
+                .collect(),
+        }
+    }
+
+    /// Y coordinate the camera follows, falls back to the first agent if focused agent was not recorded
+    fn focused_y(&self) -> f64 {
+        self.cars
+            .iter()
+            .find(|pose| pose.id == self.focused && !matches!(pose.kind, CarKind::Traffic(_)))
+            .or_else(|| {
+                self.cars
+                    .iter()
+                    .find(|pose| !matches!(pose.kind, CarKind::Traffic(_)))
+            })
+            .map(|pose| pose.y as f64)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Replay {
+    pub road: Road,
+    #[serde(with = "encoded")]
+    pub frames: Vec<Frame>,
+}
+
+impl Replay {
+    pub fn new(road: Road) -> Self {
+        Replay {
+            road,
+            frames: vec![],
+        }
+    }
+
+    /// Adds current poses of all cars as a new frame
+    ///
+    /// # Returns
+    /// * `bool` - `false` if the replay already has [`MAX_REPLAY_FRAMES`] and nothing was recorded
+    pub fn record(&mut self, traffic: &Traffic, agents: &Agents) -> bool {
+        if self.frames.len() >= MAX_REPLAY_FRAMES {
+            return false;
+        }
+        self.frames.push(Frame::capture(traffic, agents));
+        true
+    }
+
+    pub fn frames_to_bytes(frames: &[Frame]) -> Vec<u8> {
+        let poses = frames.iter().map(|f| f.cars.len()).sum::<usize>();
+        let mut bytes = Vec::with_capacity(9 + frames.len() * 8 + poses * POSE_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(REPLAY_VERSION);
+        bytes.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+
+        for frame in frames {
+            bytes.extend_from_slice(&frame.focused.to_le_bytes());
+            bytes.extend_from_slice(&(frame.cars.len() as u32).to_le_bytes());
+            for pose in frame.cars.iter() {
+                bytes.extend_from_slice(&pose.id.to_le_bytes());
+                bytes.push(pose.kind.to_byte());
+                bytes.push(pose.flags);
+                [pose.x, pose.y, pose.angle]
+                    .iter()
+                    .for_each(|value| bytes.extend_from_slice(&value.to_le_bytes()));
+            }
+        }
+
+        bytes
+    }
+
+    pub fn frames_from_bytes(bytes: &[u8]) -> Result<Vec<Frame>, String> {
+        let truncated = |_| "replay is truncated".to_string();
+        let mut reader = Reader(bytes);
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err("data is not a replay".into());
+        }
+
+        match reader.u8().map_err(truncated)? {
+            REPLAY_VERSION => (),
+            version => {
+                return Err(format!(
+                "replay format version {version} is not supported, latest supported is {REPLAY_VERSION}"
+            ))
+            }
+        }
+
+        let frames_count = reader.u32().map_err(truncated)? as usize;
+        let mut frames = Vec::with_capacity(frames_count.min(bytes.len() / 8));
+        for _ in 0..frames_count {
+            let focused = reader.u32().map_err(truncated)?;
+            let cars_count = reader.u32().map_err(truncated)? as usize;
+            // checked before allocating, so corrupted count can't make us allocate gigabytes
+            if reader.0.len() < cars_count.saturating_mul(POSE_SIZE) {
+                return Err("replay is truncated".into());
+            }
+
+            let cars = (0..cars_count)
+                .map(|_| {
+                    Ok(CarPose {
+                        id: reader.u32().map_err(truncated)?,
+                        kind: CarKind::from_byte(reader.u8().map_err(truncated)?)?,
+                        flags: reader.u8().map_err(truncated)?,
+                        x: reader.f32().map_err(truncated)?,
+                        y: reader.f32().map_err(truncated)?,
+                        angle: reader.f32().map_err(truncated)?,
+                    })
+                })
+                .collect::<Result<Vec<CarPose>, String>>()?;
+            frames.push(Frame { focused, cars });
+        }
+
+        Ok(frames)
+    }
+}
+
+/// Serde adapter storing frames as base64 string of [`Replay::frames_to_bytes`]
+mod encoded {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{Frame, Replay};
+
+    pub fn serialize<S: Serializer>(frames: &[Frame], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(Replay::frames_to_bytes(frames)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Frame>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let bytes = STANDARD
+            .decode(encoded.trim())
+            .map_err(|e| serde::de::Error::custom(format!("invalid base64: {e}")))?;
+        Replay::frames_from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Exported replay together with the save of the agent it was recorded with
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ReplayFile {
+    /// missing when the focused agent had no brain, ie. it was driven by human or by NEAT genome
+    #[serde(default)]
+    pub save: Option<Save>,
+    pub replay: Replay,
+}
+
+impl ReplayFile {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize replay")
+    }
+
+    /// Parses the replay, the save is validated the same way as [`Save::from_json`] does
+    pub fn from_json(json: &str) -> Result<ReplayFile, String> {
+        let file: ReplayFile =
+            serde_json::from_str(json).map_err(|e| format!("invalid replay: {e}"))?;
+        if let Some(save) = file.save.as_ref() {
+            save.validate()?;
+        }
+
+        Ok(file)
+    }
+}
+
+/// Plays back the recorded replay, it can be paused, sped up or slowed down and scrubbed to any frame
+#[wasm_bindgen]
+#[derive(Debug)]
+pub struct ReplayPlayer {
+    file: ReplayFile,
+    /// fractional, so the replay can be played slower than one frame per step
+    position: f64,
+    speed: f64,
+    playing: bool,
+}
+
+#[wasm_bindgen]
+impl ReplayPlayer {
+    /// Loads replay exported by `Simulation.exportReplay`
+    ///
+    /// # Returns
+    /// * `Option<ReplayPlayer>` - `None` if the replay is not valid, the reason is logged
+    pub fn load(json: &str) -> Option<ReplayPlayer> {
+        ReplayFile::from_json(json)
+            .map(ReplayPlayer::new)
+            .map_err(|e| error!("failed to load replay, {e}"))
+            .ok()
+    }
+
+    #[wasm_bindgen(getter, js_name = framesCount)]
+    pub fn frames_count(&self) -> usize {
+        self.file.replay.frames.len()
+    }
+
+    /// Index of the frame which is drawn
+    #[wasm_bindgen(getter)]
+    pub fn position(&self) -> usize {
+        self.position as usize
+    }
+
+    /// Moves to the given frame, it is clamped to the recorded ones
+    pub fn seek(&mut self, frame: usize) {
+        self.position = frame.min(self.frames_count().saturating_sub(1)) as f64;
+    }
+
+    pub fn play(&mut self) {
+        // playing finished replay starts it again
+        if self.position() + 1 >= self.frames_count() {
+            self.position = 0.;
+        }
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    #[wasm_bindgen(getter, js_name = isPlaying)]
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Recorded frames played per step, ie. `0.5` plays in half speed
+    #[wasm_bindgen(getter)]
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed.max(0.);
+    }
+
+    /// Save the replay was exported with, see `Simulation.importSave`
+    #[wasm_bindgen(js_name = exportSave)]
+    pub fn export_save(&self) -> Option<String> {
+        self.file.save.as_ref().map(Save::to_json)
+    }
+
+    /// Moves playback forward by `speed` frames, when the last frame is reached playback is paused
+    pub fn advance(&mut self) {
+        if !self.playing {
+            return;
+        }
+
+        let last = self.frames_count().saturating_sub(1) as f64;
+        self.position = (self.position + self.speed).min(last);
+        if self.position >= last {
+            self.playing = false;
+        }
+    }
+
+    /// Advances the playback and draws the current frame, counterpart of `Simulation.step`
+    pub fn step(&mut self, car_ctx: CanvasRenderingContext2d, car_rendering_distance: f64) {
+        self.advance();
+        self.draw(&car_ctx, car_rendering_distance);
+    }
+
+    /// Draws the current frame, camera follows the agent which was focused while recording
+    pub fn draw(&self, car_ctx: &CanvasRenderingContext2d, car_rendering_distance: f64) {
+        let frame = match self.frame() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let focused_y = frame.focused_y();
+
+        car_ctx.save();
+        car_ctx
+            .translate(
+                0.,
+                -focused_y + car_ctx.canvas().unwrap().height() as f64 * 0.7,
+            )
+            .expect("failed to translate on saved context");
+        self.file.replay.road.draw(car_ctx);
+
+        let visible = frame.cars.iter().filter(|pose| {
+            (focused_y.abs() - (pose.y as f64).abs()).abs() <= car_rendering_distance
+        });
+        let (traffic, agents): (Vec<&CarPose>, Vec<&CarPose>) =
+            visible.partition(|pose| matches!(pose.kind, CarKind::Traffic(_)));
+        traffic
+            .into_iter()
+            .for_each(|pose| Car::from_pose(pose).draw(car_ctx, false));
+
+        car_ctx.set_global_alpha(0.2);
+        agents
+            .iter()
+            .filter(|pose| pose.id != frame.focused)
+            .for_each(|pose| Car::from_pose(pose).draw(car_ctx, false));
+        car_ctx.set_global_alpha(1.);
+        agents
+            .iter()
+            .filter(|pose| pose.id == frame.focused)
+            .for_each(|pose| Car::from_pose(pose).draw(car_ctx, false));
+
+        car_ctx.restore();
+    }
+}
+
+impl ReplayPlayer {
+    pub fn new(file: ReplayFile) -> Self {
+        ReplayPlayer {
+            file,
+            position: 0.,
+            speed: 1.,
+            playing: false,
+        }
+    }
+
+    /// Frame at the current position, `None` if the replay is empty
+    pub fn frame(&self) -> Option<&Frame> {
+        self.file.replay.frames.get(self.position())
+    }
+}
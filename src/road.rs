@@ -7,7 +7,7 @@ use wasm_bindgen::prelude::*;
 const INFINITY: f64 = 100_000.;
 
 #[wasm_bindgen]
-#[derive(Debug)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Road {
     x: f64,
     width: f64,
@@ -18,7 +18,6 @@ pub struct Road {
     top: f64,
     bottom: f64,
     borders: Vec<((f64, f64), (f64, f64))>,
-    dash_line: Array,
 }
 
 #[wasm_bindgen]
@@ -36,10 +35,6 @@ impl Road {
             ((right, top), (right, bottom)),
         ];
 
-        let dash_line = Array::new();
-        dash_line.push(&JsValue::from(20));
-        dash_line.push(&JsValue::from(20));
-
         Self {
             x,
             width,
@@ -49,7 +44,6 @@ impl Road {
             top,
             bottom,
             borders,
-            dash_line,
         }
     }
 
@@ -81,6 +75,12 @@ impl Road {
         self.right
     }
 
+    /// Index of the lane `x` lies in, positions outside of the road are counted to the nearest lane
+    pub fn lane_index(&self, x: f64) -> i32 {
+        let lane_width = self.width / self.lane_count as f64;
+        (((x - self.left) / lane_width).floor() as i32).clamp(0, self.lane_count - 1)
+    }
+
     pub fn lane_center(&self, lane_index: i32) -> f64 {
         let lane_width = self.width / self.lane_count as f64;
         self.left + lane_width / 2. + (lane_index.min(self.lane_count - 1) as f64) * lane_width
@@ -90,10 +90,14 @@ impl Road {
         ctx.set_line_width(5.);
         ctx.set_stroke_style(&JsValue::from_str("white"));
 
+        let dash_line = Array::new();
+        dash_line.push(&JsValue::from(20));
+        dash_line.push(&JsValue::from(20));
+
         for i in 1..self.lane_count {
             let inner_x =
                 crate::utils::lerp(self.left, self.right, i as f64 / self.lane_count as f64);
-            let _ = ctx.set_line_dash(self.dash_line.as_ref());
+            let _ = ctx.set_line_dash(dash_line.as_ref());
 
             ctx.begin_path();
             ctx.move_to(inner_x, self.top);
@@ -0,0 +1,140 @@
+//! What is stored when a brain is saved, either in local storage or in an exported file
+
+use crate::{
+    ai::{population::HallOfFame, NeuralNetwork},
+    Config,
+};
+
+/// Number of controls, ie outputs every brain has to have
+const OUTPUTS_COUNT: usize = 4;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Save {
+    /// stored in the compact format, saves created before it existed store JSON and are migrated on load
+    #[serde(with = "crate::ai::format::compact")]
+    pub brain: NeuralNetwork,
+    pub config: Config,
+    #[serde(default)]
+    pub hall_of_fame: HallOfFame,
+}
+
+impl Save {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize save data")
+    }
+
+    /// Parses save and checks it can be used, see [`Save::validate`]
+    ///
+    /// # Returns
+    /// * `Result<Save, String>` - save or description of what is wrong with it
+    pub fn from_json(json: &str) -> Result<Save, String> {
+        let save = serde_json::from_str::<Save>(json).map_err(|e| format!("invalid save: {e}"))?;
+        save.validate()?;
+        Ok(save)
+    }
+
+    /// Checks that the brains fit the config and the config makes sense, saves come from files
+    /// shared between people, so we can't trust them not to crash the simulation
+    pub fn validate(&self) -> Result<(), String> {
+        let config = &self.config;
+        if config.lanes_count == 0 || config.lane_index >= config.lanes_count {
+            return Err(format!(
+                "lane index {} is not within {} lanes",
+                config.lane_index, config.lanes_count
+            ));
+        }
+
+        if config.cars_count == 0 {
+            return Err("cars count has to be at least 1".to_string());
+        }
+
+        if config.rays_count == 0 {
+            return Err("rays count has to be at least 1".to_string());
+        }
+
+        if !(0. ..=1.).contains(&config.mutation_probability) {
+            return Err(format!(
+                "mutation probability {} is not within 0 and 1",
+                config.mutation_probability
+            ));
+        }
+
+        validate_brain(&self.brain, config).map_err(|e| format!("brain {e}"))?;
+        for (i, entry) in self.hall_of_fame.entries.iter().enumerate() {
+            validate_brain(&entry.brain, config)
+                .map_err(|e| format!("brain {} of the hall of fame {e}", i + 1))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_brain(brain: &NeuralNetwork, config: &Config) -> Result<(), String> {
+    let (first, last) = match (brain.0.first(), brain.0.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return Err("has no levels".to_string()),
+    };
+
+    let inputs = first.input_count();
+    if inputs != config.rays_count {
+        return Err(format!(
+            "has {inputs} inputs, but there are {} rays",
+            config.rays_count
+        ));
+    }
+
+    let outputs = last.output_count();
+    if outputs != OUTPUTS_COUNT {
+        return Err(format!(
+            "has {outputs} outputs, but there are {OUTPUTS_COUNT} controls"
+        ));
+    }
+
+    for (index, level) in brain.0.iter().enumerate() {
+        let (inputs, outputs) = (level.input_count(), level.output_count());
+        if inputs == 0 || outputs == 0 {
+            return Err(format!(
+                "level {} has {inputs} inputs and {outputs} outputs, it needs at least one of each",
+                index + 1
+            ));
+        }
+
+        if level.weights.len() != inputs * outputs || level.biases.len() != outputs {
+            return Err(format!(
+                "level {} has {} weights and {} biases, but {inputs} inputs and {outputs} outputs",
+                index + 1,
+                level.weights.len(),
+                level.biases.len()
+            ));
+        }
+
+        let remembered = match level.recurrent.is_empty() {
+            true => 0,
+            false => outputs,
+        };
+        if level.recurrent.len() != remembered * remembered || level.memory.len() != remembered {
+            return Err(format!(
+                "level {} has {} recurrent weights and memory of {} values, but {outputs} outputs",
+                index + 1,
+                level.recurrent.len(),
+                level.memory.len()
+            ));
+        }
+
+        if let Some(previous) = index.checked_sub(1).map(|i| &brain.0[i]) {
+            if previous.output_count() != inputs {
+                return Err(format!(
+                    "level {} has {inputs} inputs, but the previous level has {} outputs",
+                    index + 1,
+                    previous.output_count()
+                ));
+            }
+        }
+    }
+
+    if brain.parameters().any(|p| !p.is_finite()) {
+        return Err("has weights which are not finite numbers".to_string());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,234 @@
+use crate::{
+    ai::{agents::fitness, NeuralNetwork},
+    car::Car,
+    road::Road,
+    traffic::Traffic,
+    vehicle::{TrafficMix, VehicleType},
+    Config, CAR_CANVAS_WIDTH_DEFAULT, CAR_HEIGHT_DEFAULT,
+};
+
+/// Gap between rows of cars in the training scenario
+const TRAINING_DISTANCE: f64 = 250.;
+/// Distance between rows of cars in the test scenario when distance ratio is 1
+const TEST_DISTANCE: f64 = 250.;
+
+/// Evaluation ends early if the agent does not get any further for this many ticks
+pub const STALL_TICKS: usize = 300;
+
+/// One vehicle of the scenario
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ScenarioVehicle {
+    pub lane: i32,
+    /// how far ahead of the scenario's start the vehicle is spawned
+    pub distance: f64,
+}
+
+/// Predefined layout of the traffic, so agents can be tested on the same situations over and over
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Scenario {
+    pub name: String,
+    pub vehicles: Vec<ScenarioVehicle>,
+    /// when set, vehicle types are picked from the mix, otherwise only regular cars are spawned
+    pub mix: Option<TrafficMix>,
+}
+
+impl Scenario {
+    pub fn new(name: &str, vehicles: Vec<ScenarioVehicle>) -> Self {
+        Scenario {
+            name: name.to_string(),
+            vehicles,
+            mix: None,
+        }
+    }
+
+    pub fn with_mix(mut self, mix: TrafficMix) -> Self {
+        self.mix = Some(mix);
+        self
+    }
+
+    /// Rows of one or two cars with gaps between the rows shrinking with `distance_ratio`
+    ///
+    /// ```text
+    /// | |x|x|
+    /// | | | |
+    /// |x| |x|
+    /// | | | |
+    /// |x|x| |
+    /// | | | |
+    /// |x| |X|
+    /// | | | |
+    /// | |x| |
+    /// | | | |
+    /// |x| |x|
+    /// ```
+    pub fn test(distance_ratio: f64) -> Self {
+        let rows: [&[i32]; 7] = [&[0, 2], &[0, 2], &[1], &[0, 2], &[0, 1], &[0, 2], &[1, 2]];
+
+        let vehicles = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(index, lanes)| {
+                lanes.iter().map(move |lane| ScenarioVehicle {
+                    lane: *lane,
+                    distance: (index + 1) as f64 * TEST_DISTANCE * distance_ratio,
+                })
+            })
+            .collect();
+
+        Scenario::new("test", vehicles)
+    }
+
+    /// Blocks of three rows of cars packed tightly together, agent has to weave between them
+    pub fn training() -> Self {
+        const SHORT: f64 = TRAINING_DISTANCE / 3.;
+
+        let blocks: [(f64, &[i32]); 16] = [
+            (0., &[0, 2]),
+            (SHORT, &[0, 2]),
+            // spread, avoid car in the middle, ie can turn left or right
+            (TRAINING_DISTANCE, &[1]),
+            (SHORT, &[1]),
+            // from spreading return to middle
+            (TRAINING_DISTANCE, &[0, 2]),
+            (SHORT, &[0, 2]),
+            // turn to right most lane
+            (TRAINING_DISTANCE, &[0, 1]),
+            (SHORT, &[0, 1]),
+            // spread
+            (TRAINING_DISTANCE, &[1]),
+            (SHORT, &[1]),
+            // return to middle
+            (TRAINING_DISTANCE, &[0, 2]),
+            (SHORT, &[0, 2]),
+            // turn to left most lane
+            (TRAINING_DISTANCE, &[1, 2]),
+            (SHORT, &[1, 2]),
+            // turn to right most lane
+            (TRAINING_DISTANCE, &[0, 1]),
+            (SHORT, &[0, 1]),
+        ];
+
+        let mut vehicles = vec![];
+        let mut distance = 150.;
+        for (gap, lanes) in blocks.iter() {
+            distance += gap;
+            for _ in 0..3 {
+                distance += CAR_HEIGHT_DEFAULT + 10.;
+                vehicles.extend(lanes.iter().map(|lane| ScenarioVehicle {
+                    lane: *lane,
+                    distance,
+                }));
+            }
+        }
+
+        Scenario::new("training", vehicles)
+    }
+
+    /// Distance of the furthest vehicle, once it is passed the scenario is over
+    pub fn length(&self) -> f64 {
+        self.vehicles.iter().map(|v| v.distance).fold(0., f64::max)
+    }
+
+    /// Y coordinate agents have to get past to finish the scenario spawned at `start_y`
+    pub fn finish_y(&self, start_y: f64) -> f64 {
+        start_y - self.length() - CAR_HEIGHT_DEFAULT * 2.
+    }
+
+    /// Road the scenarios are driven on when they are run without the simulation
+    pub fn road(config: &Config) -> Road {
+        Road::new(
+            CAR_CANVAS_WIDTH_DEFAULT / 2.,
+            CAR_CANVAS_WIDTH_DEFAULT * 0.9,
+            config.lanes_count as i32,
+        )
+    }
+
+    /// Adds scenario's vehicles to the traffic
+    ///
+    /// # Arguments
+    /// * `start_y` - y coordinate from which the distances of vehicles are measured
+    pub fn spawn(&self, road: &Road, traffic: &mut Traffic, start_y: f64) {
+        for vehicle in self.vehicles.iter() {
+            let vehicle_type = match self.mix {
+                Some(mix) => mix.random(),
+                None => VehicleType::Car,
+            };
+
+            traffic.add_vehicle(
+                vehicle_type,
+                road.lane_center(vehicle.lane),
+                start_y - vehicle.distance,
+            );
+        }
+    }
+
+    /// Drives AI car with `brain` through the scenario without drawing anything
+    ///
+    /// Drive ends when the car crashes, gets past the last vehicle, stops making progress or after `max_ticks`.
+    ///
+    /// # Returns
+    /// * `f64` - fitness the car reached, the same the agents get in the simulation
+    pub fn evaluate(&self, brain: &NeuralNetwork, config: &Config, max_ticks: usize) -> f64 {
+        let road = Scenario::road(config);
+        let mut car = Car::ai_default(
+            0,
+            road.lane_center(config.lane_index as i32),
+            Some(brain.clone()),
+            config,
+        );
+
+        let mut traffic = Traffic::new();
+        self.spawn(&road, &mut traffic, car.y);
+        let finish_y = self.finish_y(car.y);
+
+        let mut progress = Progress::new(&car);
+        for tick in 0..max_ticks {
+            traffic.update();
+            car.update(&road, &traffic);
+
+            if car.damaged || car.y < finish_y || progress.is_stalled(tick, &car) {
+                break;
+            }
+        }
+
+        fitness(&car)
+    }
+}
+
+/// Best fitness the car reached while driving through the scenario and when it did so
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    best_fitness: f64,
+    best_fitness_tick: usize,
+}
+
+impl Progress {
+    pub fn new(car: &Car) -> Self {
+        Progress {
+            best_fitness: fitness(car),
+            best_fitness_tick: 0,
+        }
+    }
+
+    /// Records fitness of the car in `tick`
+    ///
+    /// # Returns
+    /// * `bool` - car did not get any further for more than [`STALL_TICKS`] ticks
+    pub fn is_stalled(&mut self, tick: usize, car: &Car) -> bool {
+        let current_fitness = fitness(car);
+        if current_fitness > self.best_fitness {
+            self.best_fitness = current_fitness;
+            self.best_fitness_tick = tick;
+        }
+
+        tick - self.best_fitness_tick > STALL_TICKS
+    }
+}
+
+/// Scenario spawned into the running simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveScenario {
+    pub name: String,
+    /// see [`Scenario::finish_y`]
+    pub finish_y: f64,
+}
@@ -8,7 +8,7 @@ use wasm_bindgen::prelude::*;
 use web_sys::CanvasRenderingContext2d;
 
 #[wasm_bindgen]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 pub struct Ray {
     start: (f64, f64),
     end: (f64, f64),
@@ -16,7 +16,7 @@ pub struct Ray {
 }
 
 #[wasm_bindgen]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Sensor {
     ray_count: i32,
     ray_spread: f64,
@@ -139,7 +139,7 @@ fn get_reading(
 
     for car in traffic.0.iter() {
         // let's skip cars that are out of sensor's range
-        if (reader_y.abs() - car.y.abs()).abs() > ray_length + 20. {
+        if (reader_y.abs() - car.y.abs()).abs() > ray_length + car.height() / 2. {
             continue;
         }
         let poly = car.polygons();
@@ -0,0 +1,80 @@
+//! Exact state of the simulated world, it can be stored and restored later to reproduce
+//! a bug or an interesting situation
+
+use std::collections::VecDeque;
+
+use crate::{ai::agents::Agents, road::Road, telemetry::Telemetry, traffic::Traffic, Config};
+
+/// Every how many ticks the world is captured into [`RewindBuffer`]
+pub const REWIND_INTERVAL: u64 = 30;
+/// How many snapshots [`RewindBuffer`] keeps, with the interval it is about 10 seconds at 60 ticks per second
+pub const REWIND_CAPACITY: usize = 20;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Snapshot {
+    /// number of ticks the simulation ran when the snapshot was taken
+    pub tick: u64,
+    /// state of the random number generator, see [`crate::utils::random_state`]
+    pub rng_state: u64,
+    pub config: Config,
+    pub road: Road,
+    pub traffic: Traffic,
+    pub agents: Agents,
+    #[serde(default)]
+    pub telemetry: Telemetry,
+}
+
+impl Snapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("failed to serialize snapshot")
+    }
+
+    pub fn from_json(json: &str) -> Result<Snapshot, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid snapshot: {e}"))
+    }
+}
+
+/// Snapshots of the recent ticks, the world can be rewound to any tick since the oldest one
+///
+/// Capturing the whole world every tick would take too much memory, so only every [`REWIND_INTERVAL`]-th
+/// tick is stored and the ticks in between are simulated again from the closest older snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct RewindBuffer {
+    snapshots: VecDeque<Snapshot>,
+}
+
+impl RewindBuffer {
+    /// Stores the snapshot, snapshots of the same or later ticks are dropped as they belong to the
+    /// timeline which was rewound
+    pub fn push(&mut self, snapshot: Snapshot) {
+        while self
+            .snapshots
+            .back()
+            .is_some_and(|latest| latest.tick >= snapshot.tick)
+        {
+            self.snapshots.pop_back();
+        }
+
+        self.snapshots.push_back(snapshot);
+        if self.snapshots.len() > REWIND_CAPACITY {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Tick of the oldest snapshot, the world can't be rewound further
+    pub fn oldest_tick(&self) -> Option<u64> {
+        self.snapshots.front().map(|snapshot| snapshot.tick)
+    }
+
+    /// Latest snapshot taken at or before the tick
+    pub fn latest_before(&self, tick: u64) -> Option<&Snapshot> {
+        self.snapshots
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.tick <= tick)
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
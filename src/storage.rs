@@ -0,0 +1,472 @@
+//! Storage of the brain library and recorded datasets
+//!
+//! Local storage holds only a few megabytes and blocks the page while it reads or writes, so the
+//! library lives in IndexedDB. Browsers without it fall back to local storage and native tests
+//! use [`MemoryStorage`]. All of them implement [`Storage`], code using the library does not care
+//! which one it got.
+
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc};
+
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{IdbDatabase, IdbObjectStore, IdbRequest, IdbTransactionMode};
+
+use crate::{error, save::Save};
+
+const DATABASE_NAME: &str = "wasm-self-driving-car";
+const DATABASE_VERSION: u32 = 1;
+
+/// Part of the storage, each one is IndexedDB object store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Store {
+    /// saves, see [`Save`]
+    Brains,
+    /// recorded human driving, see [`crate::ai::dataset::Dataset`]
+    Datasets,
+}
+
+impl Store {
+    pub const ALL: [Store; 2] = [Store::Brains, Store::Datasets];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Store::Brains => "brains",
+            Store::Datasets => "datasets",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// browser does not provide the storage
+    Unavailable,
+    /// browser failed to read or write
+    Browser(String),
+    /// stored value can't be used, eg save does not fit its config
+    Invalid(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Unavailable => write!(f, "storage is not available"),
+            StorageError::Browser(e) => write!(f, "storage failed: {e}"),
+            StorageError::Invalid(e) => write!(f, "stored value is invalid: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<StorageError> for JsValue {
+    fn from(e: StorageError) -> Self {
+        JsValue::from_str(&e.to_string())
+    }
+}
+
+fn browser_error(e: JsValue) -> StorageError {
+    StorageError::Browser(format!("{e:?}"))
+}
+
+/// Asynchronous key-value storage of strings split into [`Store`]s
+// simulation runs in a single thread, so the futures do not have to be `Send`
+#[allow(async_fn_in_trait)]
+pub trait Storage {
+    async fn get(&self, store: Store, key: &str) -> Result<Option<String>, StorageError>;
+    async fn set(&self, store: Store, key: &str, value: &str) -> Result<(), StorageError>;
+    async fn delete(&self, store: Store, key: &str) -> Result<(), StorageError>;
+    /// All keys of the store in ascending order
+    async fn keys(&self, store: Store) -> Result<Vec<String>, StorageError>;
+}
+
+/// Stores save under `name`, replaces the save stored under the same name
+pub async fn store_save<S: Storage>(
+    storage: &S,
+    name: &str,
+    save: &Save,
+) -> Result<(), StorageError> {
+    storage.set(Store::Brains, name, &save.to_json()).await
+}
+
+/// Loads save stored under `name` and checks it can be used, see [`Save::validate`]
+pub async fn load_save<S: Storage>(storage: &S, name: &str) -> Result<Option<Save>, StorageError> {
+    match storage.get(Store::Brains, name).await? {
+        Some(json) => Save::from_json(&json)
+            .map(Some)
+            .map_err(StorageError::Invalid),
+        None => Ok(None),
+    }
+}
+
+/// Keeps everything in memory, nothing survives dropping it
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    values: RefCell<BTreeMap<(Store, String), String>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Storage for MemoryStorage {
+    async fn get(&self, store: Store, key: &str) -> Result<Option<String>, StorageError> {
+        Ok(self.values.borrow().get(&(store, key.to_string())).cloned())
+    }
+
+    async fn set(&self, store: Store, key: &str, value: &str) -> Result<(), StorageError> {
+        self.values
+            .borrow_mut()
+            .insert((store, key.to_string()), value.to_string());
+        Ok(())
+    }
+
+    async fn delete(&self, store: Store, key: &str) -> Result<(), StorageError> {
+        self.values.borrow_mut().remove(&(store, key.to_string()));
+        Ok(())
+    }
+
+    async fn keys(&self, store: Store) -> Result<Vec<String>, StorageError> {
+        Ok(self
+            .values
+            .borrow()
+            .keys()
+            .filter(|(s, _)| *s == store)
+            .map(|(_, key)| key.clone())
+            .collect())
+    }
+}
+
+/// Fallback for browsers without IndexedDB, keys are prefixed by the name of the store
+pub struct LocalStorage {
+    storage: web_sys::Storage,
+}
+
+impl LocalStorage {
+    pub fn new(window: &web_sys::Window) -> Result<Self, StorageError> {
+        let storage = window
+            .local_storage()
+            .map_err(browser_error)?
+            .ok_or(StorageError::Unavailable)?;
+        Ok(LocalStorage { storage })
+    }
+
+    fn key(store: Store, key: &str) -> String {
+        format!("{}/{}", store.name(), key)
+    }
+}
+
+impl Storage for LocalStorage {
+    async fn get(&self, store: Store, key: &str) -> Result<Option<String>, StorageError> {
+        self.storage
+            .get_item(&LocalStorage::key(store, key))
+            .map_err(browser_error)
+    }
+
+    async fn set(&self, store: Store, key: &str, value: &str) -> Result<(), StorageError> {
+        self.storage
+            .set_item(&LocalStorage::key(store, key), value)
+            .map_err(browser_error)
+    }
+
+    async fn delete(&self, store: Store, key: &str) -> Result<(), StorageError> {
+        self.storage
+            .delete(&LocalStorage::key(store, key))
+            .map_err(browser_error)
+    }
+
+    async fn keys(&self, store: Store) -> Result<Vec<String>, StorageError> {
+        let prefix = LocalStorage::key(store, "");
+        let mut keys = vec![];
+        for i in 0..self.storage.length().map_err(browser_error)? {
+            if let Some(key) = self.storage.key(i).map_err(browser_error)? {
+                if let Some(key) = key.strip_prefix(&prefix) {
+                    keys.push(key.to_string());
+                }
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+pub struct IndexedDb {
+    database: IdbDatabase,
+}
+
+impl IndexedDb {
+    /// Opens the database, creating object stores if they do not exist yet
+    pub async fn open(window: &web_sys::Window) -> Result<Self, StorageError> {
+        let factory = window
+            .indexed_db()
+            .map_err(browser_error)?
+            .ok_or(StorageError::Unavailable)?;
+        let request = factory
+            .open_with_u32(DATABASE_NAME, DATABASE_VERSION)
+            .map_err(browser_error)?;
+
+        let upgraded_request = request.clone();
+        let on_upgrade = Closure::<dyn FnMut()>::new(move || {
+            let database = match upgraded_request.result() {
+                Ok(database) => database.unchecked_into::<IdbDatabase>(),
+                Err(e) => return error!("failed to upgrade database: {e:?}"),
+            };
+
+            let existing = database.object_store_names();
+            for store in Store::ALL.iter() {
+                if !existing.contains(store.name()) {
+                    if let Err(e) = database.create_object_store(store.name()) {
+                        error!("failed to create object store '{}': {e:?}", store.name());
+                    }
+                }
+            }
+        });
+        request.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+
+        let database = request_result(&request).await;
+        request.set_onupgradeneeded(None);
+
+        Ok(IndexedDb {
+            database: database?.unchecked_into(),
+        })
+    }
+
+    fn object_store(
+        &self,
+        store: Store,
+        mode: IdbTransactionMode,
+    ) -> Result<IdbObjectStore, StorageError> {
+        self.database
+            .transaction_with_str_and_mode(store.name(), mode)
+            .and_then(|transaction| transaction.object_store(store.name()))
+            .map_err(browser_error)
+    }
+}
+
+/// Waits until the request finishes
+///
+/// # Returns
+/// * `Result<JsValue, StorageError>` - result of the request
+async fn request_result(request: &IdbRequest) -> Result<JsValue, StorageError> {
+    let mut on_success = None;
+    let mut on_error = None;
+    let promise =
+        js_sys::Promise::new(&mut |resolve: js_sys::Function, reject: js_sys::Function| {
+            on_success = Some(Closure::<dyn FnMut()>::new(move || {
+                resolve.call0(&JsValue::NULL).ok();
+            }));
+            on_error = Some(Closure::<dyn FnMut()>::new(move || {
+                reject.call0(&JsValue::NULL).ok();
+            }));
+        });
+
+    // closures have to live until the request finishes
+    request.set_onsuccess(on_success.as_ref().map(|c| c.as_ref().unchecked_ref()));
+    request.set_onerror(on_error.as_ref().map(|c| c.as_ref().unchecked_ref()));
+    let finished = JsFuture::from(promise).await;
+    request.set_onsuccess(None);
+    request.set_onerror(None);
+
+    match finished {
+        Ok(_) => request.result().map_err(browser_error),
+        Err(_) => Err(StorageError::Browser(
+            request
+                .error()
+                .ok()
+                .flatten()
+                .map(|e| e.message())
+                .unwrap_or_else(|| "request failed".to_string()),
+        )),
+    }
+}
+
+impl Storage for IndexedDb {
+    async fn get(&self, store: Store, key: &str) -> Result<Option<String>, StorageError> {
+        let request = self
+            .object_store(store, IdbTransactionMode::Readonly)?
+            .get(&JsValue::from_str(key))
+            .map_err(browser_error)?;
+        Ok(request_result(&request).await?.as_string())
+    }
+
+    async fn set(&self, store: Store, key: &str, value: &str) -> Result<(), StorageError> {
+        let request = self
+            .object_store(store, IdbTransactionMode::Readwrite)?
+            .put_with_key(&JsValue::from_str(value), &JsValue::from_str(key))
+            .map_err(browser_error)?;
+        request_result(&request).await.map(|_| ())
+    }
+
+    async fn delete(&self, store: Store, key: &str) -> Result<(), StorageError> {
+        let request = self
+            .object_store(store, IdbTransactionMode::Readwrite)?
+            .delete(&JsValue::from_str(key))
+            .map_err(browser_error)?;
+        request_result(&request).await.map(|_| ())
+    }
+
+    async fn keys(&self, store: Store) -> Result<Vec<String>, StorageError> {
+        let request = self
+            .object_store(store, IdbTransactionMode::Readonly)?
+            .get_all_keys()
+            .map_err(browser_error)?;
+        let keys = js_sys::Array::from(&request_result(&request).await?);
+        Ok(keys.iter().filter_map(|key| key.as_string()).collect())
+    }
+}
+
+/// IndexedDB if the browser has it, local storage otherwise
+pub enum BrowserStorage {
+    IndexedDb(IndexedDb),
+    Local(LocalStorage),
+}
+
+impl BrowserStorage {
+    pub async fn open(window: &web_sys::Window) -> Result<Self, StorageError> {
+        match IndexedDb::open(window).await {
+            Ok(database) => Ok(BrowserStorage::IndexedDb(database)),
+            Err(e) => {
+                error!("failed to open IndexedDB, falling back to local storage: {e}");
+                LocalStorage::new(window).map(BrowserStorage::Local)
+            }
+        }
+    }
+}
+
+impl Storage for BrowserStorage {
+    async fn get(&self, store: Store, key: &str) -> Result<Option<String>, StorageError> {
+        match self {
+            BrowserStorage::IndexedDb(storage) => storage.get(store, key).await,
+            BrowserStorage::Local(storage) => storage.get(store, key).await,
+        }
+    }
+
+    async fn set(&self, store: Store, key: &str, value: &str) -> Result<(), StorageError> {
+        match self {
+            BrowserStorage::IndexedDb(storage) => storage.set(store, key, value).await,
+            BrowserStorage::Local(storage) => storage.set(store, key, value).await,
+        }
+    }
+
+    async fn delete(&self, store: Store, key: &str) -> Result<(), StorageError> {
+        match self {
+            BrowserStorage::IndexedDb(storage) => storage.delete(store, key).await,
+            BrowserStorage::Local(storage) => storage.delete(store, key).await,
+        }
+    }
+
+    async fn keys(&self, store: Store) -> Result<Vec<String>, StorageError> {
+        match self {
+            BrowserStorage::IndexedDb(storage) => storage.keys(store).await,
+            BrowserStorage::Local(storage) => storage.keys(store).await,
+        }
+    }
+}
+
+/// Named saves and datasets kept in the browser, every method returns `Promise`
+#[wasm_bindgen]
+pub struct BrainLibrary {
+    storage: Rc<BrowserStorage>,
+}
+
+#[wasm_bindgen]
+impl BrainLibrary {
+    /// Opens the library, resolves to `BrainLibrary`
+    pub async fn open(window: web_sys::Window) -> Result<BrainLibrary, JsValue> {
+        Ok(BrainLibrary {
+            storage: Rc::new(BrowserStorage::open(&window).await?),
+        })
+    }
+
+    /// Stores save exported by [`crate::Simulation::export_save`] under `name`, rejects invalid saves
+    #[wasm_bindgen(js_name = storeSave)]
+    pub fn store_save(&self, name: String, json: String) -> js_sys::Promise {
+        let storage = self.storage.clone();
+        future_to_promise(async move {
+            let save = Save::from_json(&json).map_err(StorageError::Invalid)?;
+            store_save(storage.as_ref(), &name, &save).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Resolves to JSON of the save, which can be imported by [`crate::Simulation::import_save`],
+    /// or `undefined` if there is no save of that name
+    #[wasm_bindgen(js_name = loadSave)]
+    pub fn load_save(&self, name: String) -> js_sys::Promise {
+        let storage = self.storage.clone();
+        future_to_promise(async move {
+            Ok(load_save(storage.as_ref(), &name)
+                .await?
+                .map(|save| JsValue::from_str(&save.to_json()))
+                .unwrap_or(JsValue::UNDEFINED))
+        })
+    }
+
+    #[wasm_bindgen(js_name = deleteSave)]
+    pub fn delete_save(&self, name: String) -> js_sys::Promise {
+        self.delete(Store::Brains, name)
+    }
+
+    /// Resolves to array of names of stored saves
+    #[wasm_bindgen(js_name = saveNames)]
+    pub fn save_names(&self) -> js_sys::Promise {
+        self.keys(Store::Brains)
+    }
+
+    /// Stores recording exported by [`crate::Simulation::export_recording_json`] under `name`
+    #[wasm_bindgen(js_name = storeDataset)]
+    pub fn store_dataset(&self, name: String, json: String) -> js_sys::Promise {
+        let storage = self.storage.clone();
+        future_to_promise(async move {
+            storage.set(Store::Datasets, &name, &json).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// Resolves to JSON of the recording or `undefined` if there is no recording of that name
+    #[wasm_bindgen(js_name = loadDataset)]
+    pub fn load_dataset(&self, name: String) -> js_sys::Promise {
+        let storage = self.storage.clone();
+        future_to_promise(async move {
+            Ok(storage
+                .get(Store::Datasets, &name)
+                .await?
+                .map(|json| JsValue::from_str(&json))
+                .unwrap_or(JsValue::UNDEFINED))
+        })
+    }
+
+    #[wasm_bindgen(js_name = deleteDataset)]
+    pub fn delete_dataset(&self, name: String) -> js_sys::Promise {
+        self.delete(Store::Datasets, name)
+    }
+
+    #[wasm_bindgen(js_name = datasetNames)]
+    pub fn dataset_names(&self) -> js_sys::Promise {
+        self.keys(Store::Datasets)
+    }
+}
+
+impl BrainLibrary {
+    fn delete(&self, store: Store, name: String) -> js_sys::Promise {
+        let storage = self.storage.clone();
+        future_to_promise(async move {
+            storage.delete(store, &name).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    fn keys(&self, store: Store) -> js_sys::Promise {
+        let storage = self.storage.clone();
+        future_to_promise(async move {
+            let keys = storage.keys(store).await?;
+            Ok(keys
+                .into_iter()
+                .map(JsValue::from)
+                .collect::<js_sys::Array>()
+                .into())
+        })
+    }
+}
@@ -0,0 +1,153 @@
+//! Statistics of how each agent drives, collected every tick
+
+use std::collections::HashMap;
+
+use crate::{
+    ai::agents::Agents,
+    car::Car,
+    events::{CrashCause, Event},
+    road::Road,
+    traffic::Traffic,
+};
+
+/// Traffic car closer than this many pixels to the agent, measured between their bounding boxes, is a near miss
+const NEAR_MISS_MARGIN: f64 = 10.;
+/// Agent has to get this close to lane's center, as a ratio of lane's width, for the lane to count as changed,
+/// so driving on the lane line does not count as many lane changes
+const LANE_CENTER_RATIO: f64 = 0.3;
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AgentStats {
+    pub id: usize,
+    /// length of the driven path
+    pub distance: f64,
+    /// number of ticks the agent drove without crashing
+    pub ticks_alive: u64,
+    /// distance driven per tick alive
+    pub average_speed: f64,
+    pub lane_changes: u32,
+    /// times traffic car got within [`NEAR_MISS_MARGIN`] and the agent got away without crashing into it
+    pub near_misses: u32,
+    pub cars_overtaken: u32,
+    pub crash: Option<CrashCause>,
+    /// `[x, y]` of the crash
+    pub crash_location: Option<[f64; 2]>,
+    /// position in the last tick, distance is measured from it
+    position: [f64; 2],
+    lane: i32,
+    /// IDs of traffic cars which are near the agent
+    near: Vec<usize>,
+}
+
+impl AgentStats {
+    fn new(car: &Car, road: &Road) -> Self {
+        AgentStats {
+            id: car.id,
+            distance: 0.,
+            ticks_alive: 0,
+            average_speed: 0.,
+            lane_changes: 0,
+            near_misses: 0,
+            cars_overtaken: 0,
+            crash: None,
+            crash_location: None,
+            position: [car.x(), car.y],
+            lane: road.lane_index(car.x()),
+            near: vec![],
+        }
+    }
+
+    fn drive(&mut self, car: &Car, road: &Road, traffic: &Traffic) {
+        let [x, y] = self.position;
+        self.distance += (car.x() - x).hypot(car.y - y);
+        self.position = [car.x(), car.y];
+        if !car.damaged {
+            self.ticks_alive += 1;
+        }
+        self.average_speed = self.distance / self.ticks_alive.max(1) as f64;
+
+        let lane = road.lane_index(car.x());
+        let lane_width = road.width() / road.lane_count() as f64;
+        if lane != self.lane
+            && (car.x() - road.lane_center(lane)).abs() < lane_width * LANE_CENTER_RATIO
+        {
+            self.lane_changes += 1;
+            self.lane = lane;
+        }
+
+        let near = traffic
+            .0
+            .iter()
+            .filter(|other| {
+                (car.x() - other.x()).abs() < (car.width() + other.width()) / 2. + NEAR_MISS_MARGIN
+                    && (car.y - other.y).abs()
+                        < (car.height() + other.height()) / 2. + NEAR_MISS_MARGIN
+            })
+            .map(|other| other.id)
+            .collect::<Vec<usize>>();
+        if !car.damaged {
+            self.near_misses += self.near.iter().filter(|id| !near.contains(id)).count() as u32;
+        }
+        self.near = near;
+    }
+}
+
+/// Stats of all agents of the generation, including the ones which are no longer on the road
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Telemetry(HashMap<usize, AgentStats>);
+
+impl Telemetry {
+    /// Starts collecting stats of the agents from their current positions
+    pub fn new(agents: &Agents, road: &Road) -> Self {
+        Telemetry(
+            agents
+                .iter()
+                .map(|car| (car.id, AgentStats::new(car, road)))
+                .collect(),
+        )
+    }
+
+    /// Updates stats of the agents still driving after a tick which emitted `events`
+    pub fn record(&mut self, agents: &Agents, road: &Road, traffic: &Traffic, events: &[Event]) {
+        for car in agents.iter() {
+            let stats = self
+                .0
+                .entry(car.id)
+                .or_insert_with(|| AgentStats::new(car, road));
+            if stats.crash.is_none() {
+                stats.drive(car, road, traffic);
+            }
+        }
+
+        for event in events {
+            match event {
+                Event::Crashed {
+                    agent, cause, x, y, ..
+                } => {
+                    if let Some(stats) = self.0.get_mut(agent) {
+                        stats.crash = Some(*cause);
+                        stats.crash_location = Some([*x, *y]);
+                    }
+                }
+                Event::Overtook { agent, .. } => {
+                    if let Some(stats) = self.0.get_mut(agent) {
+                        stats.cars_overtaken += 1;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    pub fn get(&self, id: usize) -> Option<&AgentStats> {
+        self.0.get(&id)
+    }
+
+    /// Stats of all agents ordered by ID
+    pub fn all(&self) -> Vec<&AgentStats> {
+        let mut stats = self.0.values().collect::<Vec<&AgentStats>>();
+        stats.sort_by_key(|stats| stats.id);
+        stats
+    }
+}
@@ -1,41 +1,79 @@
 use wasm_bindgen::prelude::*;
 use web_sys::CanvasRenderingContext2d;
 
-use crate::{car::Car, road::Road};
+use crate::{
+    car::Car,
+    road::Road,
+    vehicle::{TrafficMix, VehicleType},
+};
 
 #[wasm_bindgen]
-#[derive(Debug)]
-pub struct Traffic(#[wasm_bindgen(skip)] pub Vec<Car>);
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(from = "SavedTraffic")]
+pub struct Traffic(#[wasm_bindgen(skip)] pub Vec<Car>, usize);
+
+/// Traffic as stored in snapshots, the older ones have only the cars without the next ID
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum SavedTraffic {
+    WithNextId(Vec<Car>, usize),
+    Cars(Vec<Car>),
+}
+
+impl From<SavedTraffic> for Traffic {
+    fn from(saved: SavedTraffic) -> Self {
+        match saved {
+            SavedTraffic::WithNextId(cars, next_id) => Traffic(cars, next_id),
+            SavedTraffic::Cars(cars) => {
+                let next_id = cars.iter().map(|c| c.id + 1).max().unwrap_or_default();
+                Traffic(cars, next_id)
+            }
+        }
+    }
+}
 
 #[wasm_bindgen]
 impl Traffic {
     pub fn new() -> Self {
-        Traffic(vec![])
+        Traffic(vec![], 0)
     }
 
     pub fn with_capacity(capacity: usize) -> Self {
-        Traffic(Vec::with_capacity(capacity))
+        Traffic(Vec::with_capacity(capacity), 0)
     }
 
-    pub fn add(&mut self, car: Car) {
+    /// Adds the car, it gets ID which no other car in the traffic had before
+    pub fn add(&mut self, mut car: Car) {
+        car.id = self.1;
+        self.1 += 1;
         self.0.push(car);
     }
 
     pub fn add_random_car(&mut self, y: f64, road: &Road) {
-        let at_lane = road.lane_center(js_sys::Math::floor(
-            js_sys::Math::random() * road.lane_count() as f64,
-        ) as i32);
-        let speed = js_sys::Math::random() * 3.;
+        let at_lane =
+            road.lane_center((crate::utils::random() * road.lane_count() as f64).floor() as i32);
+        let speed = crate::utils::random() * 3.;
 
         crate::log!("adding car at lane {} with speed {}", at_lane, speed);
 
         let car = Car::no_control(at_lane, y - 500., speed);
 
-        self.0.push(car);
+        self.add(car);
     }
 
     pub fn add_car(&mut self, x: f64, y: f64, max_speed: f64) {
-        self.0.push(Car::no_control(x, y, max_speed))
+        self.add(Car::no_control(x, y, max_speed))
+    }
+
+    #[wasm_bindgen(js_name = addVehicle)]
+    pub fn add_vehicle(&mut self, vehicle_type: VehicleType, x: f64, y: f64) {
+        self.add(Car::vehicle(vehicle_type, x, y))
+    }
+
+    /// Adds vehicle with type picked randomly based on the provided mix
+    #[wasm_bindgen(js_name = addMixedVehicle)]
+    pub fn add_mixed_vehicle(&mut self, x: f64, y: f64, mix: &TrafficMix) {
+        self.add_vehicle(mix.random(), x, y)
     }
 
     pub fn update(&mut self) {
@@ -61,6 +99,11 @@ impl Traffic {
         }
     }
 
+    /// Removes all the cars, the new ones still get IDs which were not used before
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
     pub fn clean(&mut self, y: f64) {
         self.0.retain(|car| car.y.abs() > y.abs() - 500.);
     }
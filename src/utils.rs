@@ -32,6 +32,81 @@ impl<'a> Drop for Timer<'a> {
 
 pub type Borders = [((f64, f64), (f64, f64))];
 
+thread_local! {
+    /// State of our pseudo random number generator, zero means it was not seeded yet
+    static RNG_STATE: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+/// Returns pseudo random number in range `0..1`
+///
+/// We use our own seedable generator (splitmix64) instead of `Math.random()`, so the same code runs
+/// natively and in the browser and runs can be reproduced by seeding it with [`seed_random`].
+pub fn random() -> f64 {
+    RNG_STATE.with(|state| {
+        if state.get() == 0 {
+            state.set(initial_seed());
+        }
+
+        let next = state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state.set(next);
+
+        let mut z = next;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+
+        // take 53 most significant bits, that is the precision of f64
+        (z >> 11) as f64 / (1_u64 << 53) as f64
+    })
+}
+
+/// Returns normally distributed pseudo random number with zero mean and unit variance (Box-Muller transform)
+pub fn gaussian() -> f64 {
+    // `random` can return 0, logarithm of which is infinite
+    let u = 1. - random();
+    let v = random();
+    (-2. * u.ln()).sqrt() * (2. * std::f64::consts::PI * v).cos()
+}
+
+/// Seeds random number generator, same seed produces the same sequence of numbers
+pub fn seed_random(seed: u64) {
+    // zero is reserved for not seeded generator
+    RNG_STATE.with(|state| state.set(seed.max(1)));
+}
+
+/// Current state of the random number generator, can be restored with [`seed_random`]
+pub fn random_state() -> u64 {
+    RNG_STATE.with(|state| state.get())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn initial_seed() -> u64 {
+    (js_sys::Math::random() * u64::MAX as f64) as u64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn initial_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(1)
+}
+
+/// Milliseconds since the Unix epoch, only differences between two values are meaningful
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+/// Milliseconds since the Unix epoch, only differences between two values are meaningful
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.)
+        .unwrap_or_default()
+}
+
 #[wasm_bindgen::prelude::wasm_bindgen]
 pub fn lerp(a: f64, b: f64, t: f64) -> f64 {
     a + t * (b - a)
@@ -96,6 +171,11 @@ pub fn poly_intersection_with_poly(poly1: &[(f64, f64)], poly2: &[(f64, f64)]) -
 }
 
 pub fn get_rgba(value: f64) -> JsValue {
+    JsValue::from(rgba(value))
+}
+
+/// CSS color of the value, green for positive values, blue for negative ones, the bigger the value the less transparent
+pub fn rgba(value: f64) -> String {
     let alpha = value.abs();
     let r = !value.is_sign_negative() as u8 * 255;
 
@@ -103,5 +183,5 @@ pub fn get_rgba(value: f64) -> JsValue {
 
     let b = !value.is_sign_positive() as u8 * 255;
 
-    JsValue::from(format!("rgba({r}, 255, {b}, {alpha})"))
+    format!("rgba({r}, 255, {b}, {alpha})")
 }
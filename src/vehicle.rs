@@ -0,0 +1,162 @@
+use wasm_bindgen::prelude::*;
+
+/// Class of the vehicle, decides its dimensions, speed and how is it drawn
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub enum VehicleType {
+    Motorcycle,
+    #[default]
+    Car,
+    Van,
+    Truck,
+    Bus,
+}
+
+impl VehicleType {
+    pub const ALL: [VehicleType; 5] = [
+        VehicleType::Motorcycle,
+        VehicleType::Car,
+        VehicleType::Van,
+        VehicleType::Truck,
+        VehicleType::Bus,
+    ];
+
+    pub fn width(&self) -> f64 {
+        match self {
+            VehicleType::Motorcycle => 12.,
+            VehicleType::Car => crate::CAR_WIDHT_DEFAULT,
+            VehicleType::Van => 34.,
+            VehicleType::Truck => 40.,
+            VehicleType::Bus => 40.,
+        }
+    }
+
+    pub fn height(&self) -> f64 {
+        match self {
+            VehicleType::Motorcycle => 28.,
+            VehicleType::Car => crate::CAR_HEIGHT_DEFAULT,
+            VehicleType::Van => 65.,
+            VehicleType::Truck => 120.,
+            VehicleType::Bus => 110.,
+        }
+    }
+
+    /// Max speed of the vehicle when it is part of the traffic
+    pub fn max_speed(&self) -> f64 {
+        match self {
+            VehicleType::Motorcycle => 2.6,
+            VehicleType::Car => 2.,
+            VehicleType::Van => 1.8,
+            VehicleType::Truck => 1.5,
+            VehicleType::Bus => 1.4,
+        }
+    }
+
+    pub fn color(&self) -> &'static str {
+        match self {
+            VehicleType::Motorcycle => "darkorange",
+            VehicleType::Car => "red",
+            VehicleType::Van => "mediumpurple",
+            VehicleType::Truck => "darkgreen",
+            VehicleType::Bus => "gold",
+        }
+    }
+
+    /// Where the cab (or windshield) ends, as a ratio of vehicle's length measured from the front
+    pub fn cab_ratio(&self) -> Option<f64> {
+        match self {
+            VehicleType::Van => Some(0.3),
+            VehicleType::Truck => Some(0.2),
+            VehicleType::Bus => Some(0.1),
+            _ => None,
+        }
+    }
+}
+
+/// Relative probabilities of vehicle types when spawning traffic
+///
+/// Values do not have to sum up to 1, they are normalized when picking a vehicle.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TrafficMix {
+    pub motorcycle: f64,
+    pub car: f64,
+    pub van: f64,
+    pub truck: f64,
+    pub bus: f64,
+}
+
+#[wasm_bindgen]
+impl TrafficMix {
+    #[wasm_bindgen(constructor)]
+    pub fn new(motorcycle: f64, car: f64, van: f64, truck: f64, bus: f64) -> Self {
+        TrafficMix {
+            motorcycle,
+            car,
+            van,
+            truck,
+            bus,
+        }
+    }
+
+    /// Mix with every vehicle type being equally likely
+    pub fn uniform() -> Self {
+        TrafficMix::new(1., 1., 1., 1., 1.)
+    }
+}
+
+impl TrafficMix {
+    fn weight(&self, vehicle_type: VehicleType) -> f64 {
+        let weight = match vehicle_type {
+            VehicleType::Motorcycle => self.motorcycle,
+            VehicleType::Car => self.car,
+            VehicleType::Van => self.van,
+            VehicleType::Truck => self.truck,
+            VehicleType::Bus => self.bus,
+        };
+
+        weight.max(0.)
+    }
+
+    /// Picks vehicle type based on the mix probabilities
+    ///
+    /// # Arguments
+    /// * `roll` - random number in range `0..1`
+    pub fn pick(&self, roll: f64) -> VehicleType {
+        let total = VehicleType::ALL
+            .iter()
+            .map(|t| self.weight(*t))
+            .sum::<f64>();
+        if total <= 0. {
+            return VehicleType::default();
+        }
+
+        let mut threshold = roll * total;
+        for vehicle_type in VehicleType::ALL {
+            let weight = self.weight(vehicle_type);
+            if threshold < weight {
+                return vehicle_type;
+            }
+            threshold -= weight;
+        }
+
+        // rounding errors can get us here, last type with non zero weight is the right answer
+        VehicleType::ALL
+            .iter()
+            .rev()
+            .copied()
+            .find(|t| self.weight(*t) > 0.)
+            .unwrap_or_default()
+    }
+
+    pub fn random(&self) -> VehicleType {
+        self.pick(crate::utils::random())
+    }
+}
+
+impl Default for TrafficMix {
+    /// only regular cars, same as traffic was before vehicle types were introduced
+    fn default() -> Self {
+        TrafficMix::new(0., 1., 0., 0., 0.)
+    }
+}
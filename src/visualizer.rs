@@ -1,4 +1,5 @@
-use crate::ai::*;
+use crate::ai::{neat::*, population::GenerationStats, *};
+use std::collections::HashMap;
 use std::f64::consts::PI;
 use wasm_bindgen::prelude::*;
 use web_sys::CanvasRenderingContext2d;
@@ -50,6 +51,191 @@ impl Visualizer {
             )
         }
     }
+
+    /// Draws NEAT genome, inputs are at the bottom, outputs at the top and hidden nodes
+    /// in between, the deeper the node is the higher it is drawn
+    pub fn draw_genome(ctx: &CanvasRenderingContext2d, genome: &Genome) {
+        let width = ctx.canvas().unwrap().width() - MARGIN * 2;
+        let height = ctx.canvas().unwrap().height() - MARGIN * 2;
+        let right = LEFT + width;
+
+        let levels = genome.levels();
+        let output_depth = levels.len() - 1;
+
+        let mut positions = HashMap::new();
+        for (depth, ids) in levels.iter().enumerate() {
+            let y = TOP as f64
+                + crate::utils::lerp(height as f64, 0., depth as f64 / output_depth as f64);
+            for (i, id) in ids.iter().enumerate() {
+                positions.insert(*id, (get_node(ids.len(), i, right), y));
+            }
+        }
+
+        let array = js_sys::Array::new();
+        array.push(&JsValue::from(7));
+        array.push(&JsValue::from(3));
+        ctx.set_line_dash(&array)
+            .expect("failed to set line dash while drawing genome");
+
+        for connection in genome.connections.iter().filter(|c| c.enabled) {
+            let (from_x, from_y) = positions[&connection.from];
+            let (to_x, to_y) = positions[&connection.to];
+            ctx.begin_path();
+            ctx.move_to(from_x, from_y);
+            ctx.line_to(to_x, to_y);
+
+            ctx.set_line_width(2.);
+            ctx.set_stroke_style_str(&crate::utils::rgba(connection.weight));
+            ctx.stroke();
+        }
+
+        ctx.set_line_dash(&js_sys::Array::new())
+            .expect("failed to `set_line_dash`");
+
+        let values = genome.values.borrow();
+        for (i, node) in genome.outputs().enumerate() {
+            let (x, y) = positions[&node.id];
+            draw_node(ctx, x, y, values.get(&node.id).copied().unwrap_or_default());
+
+            if let Some(icon) = ARROWS.get(i) {
+                ctx.begin_path();
+                ctx.set_text_align("center");
+                ctx.set_text_baseline("middle");
+                ctx.set_fill_style_str("black");
+                ctx.set_stroke_style_str("white");
+                ctx.set_font("27px Arial");
+                ctx.fill_text(icon, x, y + NODE_RADIUS * 0.1)
+                    .expect("failed to `fill_text`");
+                ctx.set_line_width(0.5);
+                ctx.stroke_text(icon, x, y + NODE_RADIUS * 0.1)
+                    .expect("failed to `fill_text`");
+            }
+        }
+
+        for node in genome.nodes.iter().filter(|n| n.kind != NodeKind::Output) {
+            let (x, y) = positions[&node.id];
+            draw_node(ctx, x, y, values.get(&node.id).copied().unwrap_or_default());
+        }
+    }
+}
+
+/// Label, color and value of a line in the fitness chart
+type Series = (&'static str, &'static str, fn(&GenerationStats) -> f64);
+
+const FITNESS_SERIES: [Series; 4] = [
+    ("best", "green", |stats| stats.best_fitness),
+    ("mean", "blue", |stats| stats.mean_fitness),
+    ("median", "orange", |stats| stats.median_fitness),
+    ("worst", "red", |stats| stats.worst_fitness),
+];
+
+impl Visualizer {
+    /// Draws best, mean, median and worst fitness of each generation as line chart,
+    /// generations go along the x axis, fitness along the y axis
+    pub fn draw_fitness_chart(ctx: &CanvasRenderingContext2d, history: &[GenerationStats]) {
+        let width = ctx.canvas().unwrap().width() as f64;
+        let height = ctx.canvas().unwrap().height() as f64;
+        let (left, top) = (LEFT as f64, TOP as f64);
+        let (right, bottom) = (width - MARGIN as f64, height - MARGIN as f64);
+
+        ctx.clear_rect(0., 0., width, height);
+        ctx.set_line_dash(&js_sys::Array::new())
+            .expect("failed to `set_line_dash`");
+
+        ctx.begin_path();
+        ctx.move_to(left, top);
+        ctx.line_to(left, bottom);
+        ctx.line_to(right, bottom);
+        ctx.set_line_width(1.);
+        ctx.set_stroke_style_str("black");
+        ctx.stroke();
+
+        ctx.set_font("12px Arial");
+        ctx.set_fill_style_str("black");
+        let (first, last) = match (history.first(), history.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => {
+                ctx.set_text_align("center");
+                ctx.fill_text(
+                    "no finished generation",
+                    (left + right) / 2.,
+                    (top + bottom) / 2.,
+                )
+                .expect("failed to `fill_text`");
+                return;
+            }
+        };
+
+        let (min, max) = history
+            .iter()
+            .flat_map(|stats| FITNESS_SERIES.iter().map(move |(_, _, value)| value(stats)))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| {
+                (min.min(value), max.max(value))
+            });
+        // flat history would divide by zero
+        let span = (max - min).max(1.);
+        let x = |index: usize| {
+            crate::utils::lerp(
+                left,
+                right,
+                match history.len() {
+                    1 => 0.5,
+                    len => index as f64 / (len - 1) as f64,
+                },
+            )
+        };
+        let y = |value: f64| crate::utils::lerp(bottom, top, (value - min) / span);
+
+        ctx.set_text_align("right");
+        ctx.set_text_baseline("middle");
+        ctx.fill_text(&format!("{max:.0}"), left - 5., y(max))
+            .expect("failed to `fill_text`");
+        ctx.fill_text(&format!("{min:.0}"), left - 5., y(min))
+            .expect("failed to `fill_text`");
+        ctx.set_text_align("center");
+        ctx.set_text_baseline("top");
+        ctx.fill_text(&first.generation.to_string(), x(0), bottom + 5.)
+            .expect("failed to `fill_text`");
+        ctx.fill_text(
+            &last.generation.to_string(),
+            x(history.len() - 1),
+            bottom + 5.,
+        )
+        .expect("failed to `fill_text`");
+
+        for (i, (label, color, value)) in FITNESS_SERIES.iter().enumerate() {
+            ctx.begin_path();
+            for (index, stats) in history.iter().enumerate() {
+                match index {
+                    0 => ctx.move_to(x(index), y(value(stats))),
+                    _ => ctx.line_to(x(index), y(value(stats))),
+                }
+            }
+            ctx.set_line_width(2.);
+            ctx.set_stroke_style_str(color);
+            ctx.stroke();
+
+            // legend in the top right corner
+            ctx.set_fill_style_str(color);
+            ctx.set_text_align("right");
+            ctx.set_text_baseline("top");
+            ctx.fill_text(label, right, top - MARGIN as f64 + 5. + i as f64 * 14.)
+                .expect("failed to `fill_text`");
+        }
+    }
+}
+
+fn draw_node(ctx: &CanvasRenderingContext2d, x: f64, y: f64, value: f64) {
+    ctx.begin_path();
+    ctx.arc(x, y, NODE_RADIUS, 0., 2. * PI)
+        .expect("failed to `arc`");
+    ctx.set_fill_style_str("black");
+    ctx.fill();
+    ctx.begin_path();
+    ctx.arc(x, y, NODE_RADIUS * 0.6, 0., 2. * PI)
+        .expect("failed to `arc`");
+    ctx.set_fill_style_str(&crate::utils::rgba(value));
+    ctx.fill();
 }
 
 fn draw_level(
@@ -64,17 +250,52 @@ fn draw_level(
     let bottom = top + height;
 
     for (i, _) in level.inputs.iter().enumerate() {
-        for (j, _) in level.outputs.borrow().iter().enumerate() {
+        for (j, _) in level.outputs.iter().enumerate() {
             ctx.begin_path();
             ctx.move_to(get_node(level.inputs.len(), i, right), bottom);
-            ctx.line_to(get_node(level.outputs.borrow().len(), j, right), top);
+            ctx.line_to(get_node(level.outputs.len(), j, right), top);
 
             ctx.set_line_width(2.);
-            ctx.set_stroke_style(&crate::utils::get_rgba(level.weights[i][j]));
+            ctx.set_stroke_style(&crate::utils::get_rgba(level.weight(i, j)));
             ctx.stroke();
         }
     }
 
+    // recurrent connections lead from outputs of the previous tick back to the outputs, they are drawn
+    // as arcs above the outputs, connection of an output to itself as a small loop
+    if level.is_recurrent() {
+        let count = level.output_count();
+        for from in 0..count {
+            for to in 0..count {
+                let from_x = get_node(count, from, right);
+                let to_x = get_node(count, to, right);
+                ctx.begin_path();
+                if from == to {
+                    ctx.arc(
+                        from_x,
+                        top - NODE_RADIUS * 1.5,
+                        NODE_RADIUS * 0.8,
+                        0.,
+                        2. * PI,
+                    )
+                    .expect("failed to `arc`");
+                } else {
+                    ctx.move_to(from_x, top);
+                    ctx.quadratic_curve_to(
+                        (from_x + to_x) / 2.,
+                        top - NODE_RADIUS - (from_x - to_x).abs() / 4.,
+                        to_x,
+                        top,
+                    );
+                }
+
+                ctx.set_line_width(1.);
+                ctx.set_stroke_style_str(&crate::utils::rgba(level.recurrent_weight(from, to)));
+                ctx.stroke();
+            }
+        }
+    }
+
     for (i, input) in level.inputs.iter().enumerate() {
         let x = get_node(level.inputs.len(), i, right);
         ctx.begin_path();
@@ -89,8 +310,8 @@ fn draw_level(
         ctx.fill();
     }
 
-    for (i, output) in level.outputs.borrow().iter().enumerate() {
-        let x = get_node(level.outputs.borrow().len(), i, right);
+    for (i, output) in level.outputs.iter().enumerate() {
+        let x = get_node(level.outputs.len(), i, right);
         ctx.begin_path();
         ctx.arc(x, top, NODE_RADIUS, 0., 2. * PI)
             .expect("failed to `arc`");
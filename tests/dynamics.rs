@@ -0,0 +1,53 @@
+//! Native tests of the bicycle model steering
+
+use wasm_self_driving_car::dynamics::{BicycleModel, WHEELBASE_MIN};
+
+#[test]
+fn centered_wheel_drives_straight() {
+    let mut model = BicycleModel::new(35., 0.35, 0.04);
+    assert_eq!(model.steer(false, false, 3.), 0.);
+    assert_eq!(model.steer(true, true, 3.), 0.);
+    assert_eq!(model.steering_angle(), 0.);
+    assert_eq!(model.turning_radius(), None);
+}
+
+#[test]
+fn turning_radius_follows_steering_angle() {
+    let mut model = BicycleModel::new(35., 0.35, 0.1);
+    let turn = model.steer(true, false, 3.);
+    assert_eq!(model.steering_angle(), 0.1);
+    assert_eq!(turn, 3. / 35. * 0.1_f64.tan());
+    assert_eq!(model.turning_radius(), Some(35. / 0.1_f64.tan()));
+
+    // wheel stops at the lock
+    for _ in 0..10 {
+        model.steer(false, true, 3.);
+    }
+    assert_eq!(model.steering_angle(), -0.35);
+    assert_eq!(model.turning_radius(), Some(35. / 0.35_f64.tan()));
+    // slower car turns less per tick, reversing car turns the other way
+    assert!(model.steer(false, true, 1.).abs() < model.steer(false, true, 3.).abs());
+    assert!(model.steer(false, true, -3.) > 0.);
+
+    // released wheel returns back to the center
+    for _ in 0..3 {
+        model.steer(false, false, 3.);
+    }
+    assert!(model.steering_angle() < 0.);
+    model.steer(false, false, 3.);
+    assert_eq!(model.steering_angle(), 0.);
+    assert_eq!(model.turning_radius(), None);
+}
+
+#[test]
+fn invalid_wheelbase_is_clamped() {
+    for wheelbase in [0., -35., f64::NAN] {
+        let mut model = BicycleModel::new(wheelbase, 0.35, 0.1);
+        assert_eq!(model.wheelbase, WHEELBASE_MIN);
+        assert!(model.steer(true, false, 3.).is_finite());
+    }
+
+    let mut model = BicycleModel::new(35., 0.35, 0.1);
+    model.wheelbase = 0.;
+    assert!(model.steer(true, false, 3.).is_finite());
+}
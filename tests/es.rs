@@ -0,0 +1,41 @@
+//! Native tests of the evolution strategy
+
+use wasm_self_driving_car::ai::{es::EvolutionStrategy, NeuralNetwork};
+use wasm_self_driving_car::utils::seed_random;
+
+fn distance_to_target(brain: &NeuralNetwork) -> f64 {
+    brain.parameters().map(|p| (p - 0.5).powi(2)).sum::<f64>()
+}
+
+#[test]
+fn ask_samples_population_around_mean() {
+    seed_random(1);
+    let start = NeuralNetwork::new(&[3, 2]);
+    let mut es = EvolutionStrategy::new(&start, 0.1, 0.1, 9);
+
+    let samples = es.ask();
+    assert_eq!(samples.len(), 10);
+    assert!(samples
+        .iter()
+        .all(|s| s.parameters_count() == start.parameters_count()));
+}
+
+#[test]
+fn tell_moves_mean_towards_better_samples() {
+    seed_random(2);
+    let mut es = EvolutionStrategy::new(&NeuralNetwork::new(&[3, 2]), 0.1, 0.03, 20);
+    let before = distance_to_target(&es.mean());
+
+    for _ in 0..100 {
+        let fitness = es
+            .ask()
+            .iter()
+            .map(|b| -distance_to_target(b))
+            .collect::<Vec<f64>>();
+        es.tell(&fitness);
+    }
+
+    let after = distance_to_target(&es.mean());
+    assert!(after < before / 10., "distance {} -> {}", before, after);
+    assert_eq!(es.generation(), 100);
+}
@@ -0,0 +1,136 @@
+//! Native tests of events emitted by the simulation
+
+use wasm_self_driving_car::events::{CrashCause, Event};
+use wasm_self_driving_car::traffic::Traffic;
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::vehicle::VehicleType;
+use wasm_self_driving_car::{Config, Simulation};
+
+fn training_run(ticks: usize) -> (Simulation, Vec<Event>) {
+    seed_random(12);
+    let mut simulation = Simulation::headless(&Config {
+        cars_count: 50,
+        ..Config::default()
+    });
+    simulation.training_traffic();
+    simulation.run();
+    for _ in 0..ticks {
+        simulation.update();
+    }
+    let events = simulation.drain_events();
+    (simulation, events)
+}
+
+#[test]
+fn run_emits_crashes_overtakes_and_finish() {
+    let (mut simulation, events) = training_run(3000);
+
+    let crashed = events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Crashed { agent, cause, .. } => Some((*agent, *cause)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert!(crashed
+        .iter()
+        .any(|(_, cause)| *cause == CrashCause::Border));
+    // every agent crashes only once
+    let mut agents = crashed.iter().map(|(agent, _)| *agent).collect::<Vec<_>>();
+    agents.sort_unstable();
+    agents.dedup();
+    assert_eq!(agents.len(), crashed.len());
+
+    assert!(events.iter().any(|e| matches!(e, Event::Overtook { .. })));
+    assert!(events.iter().any(|e| matches!(e, Event::NewBest { .. })));
+    assert_eq!(
+        events
+            .iter()
+            .filter(|e| matches!(e, Event::ScenarioFinished { name, .. } if name == "training"))
+            .count(),
+        1
+    );
+
+    // events are queued in the order they happened and draining empties the queue
+    assert!(events
+        .windows(2)
+        .all(|pair| tick(&pair[0]).unwrap_or(0) <= tick(&pair[1]).unwrap_or(u64::MAX)));
+    assert!(simulation.drain_events().is_empty());
+
+    let json: serde_json::Value = serde_json::from_str(&simulation.drain_events_json()).unwrap();
+    assert_eq!(json, serde_json::json!([]));
+    assert_eq!(
+        serde_json::to_value(&events[0]).unwrap()["type"],
+        match events[0] {
+            Event::Crashed { .. } => "crashed",
+            Event::Overtook { .. } => "overtook",
+            Event::NewBest { .. } => "newBest",
+            _ => unreachable!("first events come from driving"),
+        }
+    );
+}
+
+fn tick(event: &Event) -> Option<u64> {
+    match event {
+        Event::Crashed { tick, .. }
+        | Event::Overtook { tick, .. }
+        | Event::NewBest { tick, .. }
+        | Event::ScenarioFinished { tick, .. } => Some(*tick),
+        Event::GenerationEnded { .. } => None,
+    }
+}
+
+#[test]
+fn next_generation_emits_generation_ended() {
+    let (mut simulation, _) = training_run(100);
+    let stats = simulation.next_generation().unwrap();
+
+    assert_eq!(
+        simulation.drain_events(),
+        vec![Event::GenerationEnded {
+            generation: 0,
+            best_fitness: stats.best_fitness,
+            mean_fitness: stats.mean_fitness,
+        }]
+    );
+}
+
+#[test]
+fn traffic_cars_get_unique_ids() {
+    let mut traffic = Traffic::new();
+    for i in 0..5 {
+        traffic.add_vehicle(VehicleType::Van, 0., i as f64 * -100.);
+    }
+    traffic.add_car(0., -600., 1.);
+    let ids = traffic.0.iter().map(|car| car.id).collect::<Vec<usize>>();
+    assert_eq!(ids, vec![0, 1, 2, 3, 4, 5]);
+}
+
+#[test]
+fn traffic_ids_are_not_reused_after_cars_are_removed() {
+    let mut traffic = Traffic::new();
+    traffic.add_car(0., 0., 1.);
+    traffic.add_car(0., -1000., 1.);
+    // both cars fall behind and are cleaned up, including the one with the highest ID
+    traffic.clean(-2000.);
+    traffic.add_car(0., -3000., 1.);
+    let ids = traffic.0.iter().map(|car| car.id).collect::<Vec<usize>>();
+    assert_eq!(ids, vec![2]);
+
+    traffic.clear();
+    traffic.add_car(0., 0., 1.);
+    assert_eq!(traffic.0[0].id, 3);
+
+    // snapshots keep the next ID
+    let json = serde_json::to_string(&traffic).unwrap();
+    let mut restored: Traffic = serde_json::from_str(&json).unwrap();
+    restored.clear();
+    restored.add_car(0., 0., 1.);
+    assert_eq!(restored.0[0].id, 4);
+
+    // older snapshots have only the cars, new IDs follow the highest one
+    let cars = serde_json::to_string(&traffic.0).unwrap();
+    let mut restored: Traffic = serde_json::from_str(&cars).unwrap();
+    restored.add_car(0., 0., 1.);
+    assert_eq!(restored.0[1].id, 4);
+}
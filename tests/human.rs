@@ -0,0 +1,106 @@
+//! Native tests of the car driven by keyboard
+
+use wasm_self_driving_car::ai::agents::{Agents, HUMAN_AGENT_ID};
+use wasm_self_driving_car::car::Car;
+use wasm_self_driving_car::controls::KeyEvent;
+use wasm_self_driving_car::road::Road;
+use wasm_self_driving_car::traffic::Traffic;
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::{Config, Simulation};
+
+fn race() -> (Agents, Road, Config) {
+    seed_random(3);
+    let config = Config {
+        cars_count: 3,
+        ..Config::default()
+    };
+    let road = Road::new(100., 180., 3);
+    let mut cars = Car::generate_cars_same(road.lane_center(1), None, &config);
+    // agents stay at the start, so the human is always ahead of them
+    cars.iter_mut().for_each(|car| car.damaged = true);
+
+    let mut agents = Agents::new(cars);
+    agents.add_human(Car::human(HUMAN_AGENT_ID, road.lane_center(1), &config));
+    (agents, road, config)
+}
+
+#[test]
+fn keys_drive_human_car() {
+    let (mut agents, road, _) = race();
+    let traffic = Traffic::new();
+    let start = agents.human().unwrap().y;
+
+    agents.handle_key_input(KeyEvent::UpPressed);
+    assert!(agents.human().unwrap().controls().up);
+    assert!(agents
+        .iter()
+        .filter(|car| !car.is_human())
+        .all(|car| !car.controls().up));
+
+    for _ in 0..20 {
+        agents.update(&road, &traffic);
+    }
+    let driven = agents.human().unwrap().y;
+    assert!(driven < start);
+
+    agents.handle_key_input(KeyEvent::UpReleased);
+    assert!(!agents.human().unwrap().controls().up);
+}
+
+#[test]
+fn human_is_never_best_agent_nor_bred() {
+    let (mut agents, road, _) = race();
+    let traffic = Traffic::new();
+    agents.handle_key_input(KeyEvent::UpPressed);
+    for _ in 0..20 {
+        agents.update(&road, &traffic);
+    }
+
+    // human is ahead and scored like the agents, but does not lead them
+    let human_score = agents.score(HUMAN_AGENT_ID).unwrap();
+    assert!(agents
+        .iter()
+        .filter(|car| !car.is_human())
+        .all(|car| human_score > -car.y));
+    assert!(!agents.best_agent().unwrap().is_human());
+    assert_eq!(agents.population().count(), 3);
+    assert!(agents.population().all(|car| !car.is_human()));
+}
+
+#[test]
+fn second_human_replaces_first() {
+    let (mut agents, road, config) = race();
+    let traffic = Traffic::new();
+    agents.handle_key_input(KeyEvent::UpPressed);
+    for _ in 0..20 {
+        agents.update(&road, &traffic);
+    }
+
+    agents.add_human(Car::human(HUMAN_AGENT_ID, road.lane_center(0), &config));
+    let humans = agents
+        .iter()
+        .filter(|car| car.is_human())
+        .collect::<Vec<_>>();
+    assert_eq!(humans.len(), 1);
+    assert_eq!(humans[0].x(), road.lane_center(0));
+    assert_eq!(humans[0].y, wasm_self_driving_car::CAR_Y_DEFAULT);
+    // new car starts without pressed keys nor score of the previous one
+    assert!(!humans[0].controls().up);
+    assert_eq!(agents.score(HUMAN_AGENT_ID), None);
+}
+
+#[test]
+fn simulation_spawns_human_car() {
+    let mut simulation = Simulation::headless(&Config::default());
+    assert_eq!(simulation.human_score(), None);
+
+    simulation.spawn_human_car();
+    simulation.spawn_human_car();
+    simulation.run();
+    simulation.handle_key_input(KeyEvent::UpPressed);
+    simulation.update();
+    assert!(simulation.human_score().is_some());
+
+    let agents = simulation.capture_snapshot().agents;
+    assert_eq!(agents.iter().filter(|car| car.is_human()).count(), 1);
+}
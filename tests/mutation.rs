@@ -0,0 +1,107 @@
+//! Native tests of the mutation operators
+
+use wasm_self_driving_car::ai::{
+    mutation::{Mutation, MutationOperator, MutationSchedule},
+    population::weight_distance,
+    NeuralNetwork,
+};
+use wasm_self_driving_car::utils::seed_random;
+
+fn mutation(operator: MutationOperator, probability: f64) -> Mutation {
+    Mutation {
+        operator,
+        probability,
+        ..Mutation::lerp(0.2)
+    }
+}
+
+fn changed(a: &NeuralNetwork, b: &NeuralNetwork) -> usize {
+    a.parameters()
+        .zip(b.parameters())
+        .filter(|(a, b)| a != b)
+        .count()
+}
+
+#[test]
+fn zero_probability_keeps_brain() {
+    seed_random(1);
+    let brain = NeuralNetwork::new(&[5, 6, 4]);
+    for operator in [
+        MutationOperator::Lerp,
+        MutationOperator::Gaussian,
+        MutationOperator::Reset,
+    ]
+    .iter()
+    {
+        let mutated = brain.mutate_with(&mutation(*operator, 0.));
+        assert_eq!(weight_distance(&brain, &mutated), 0.);
+    }
+}
+
+#[test]
+fn probability_limits_number_of_mutated_weights() {
+    seed_random(2);
+    let brain = NeuralNetwork::new(&[20, 20, 4]);
+    let mutated = brain.mutate_with(&mutation(MutationOperator::Reset, 0.1));
+
+    let ratio = changed(&brain, &mutated) as f64 / brain.parameters_count() as f64;
+    assert!((0.05..0.15).contains(&ratio), "ratio {}", ratio);
+}
+
+#[test]
+fn gaussian_noise_follows_sigma() {
+    seed_random(3);
+    let brain = NeuralNetwork::new(&[20, 20, 4]);
+    let mut gaussian = mutation(MutationOperator::Gaussian, 1.);
+    gaussian.sigma = 0.01;
+
+    let distance = weight_distance(&brain, &brain.mutate_with(&gaussian));
+    assert!((0.008..0.012).contains(&distance), "distance {}", distance);
+}
+
+#[test]
+fn self_adaptive_mutation_evolves_sigma() {
+    seed_random(4);
+    let brain = NeuralNetwork::new(&[5, 6, 4]);
+    let mutated = brain.mutate_with(&mutation(MutationOperator::SelfAdaptive, 1.));
+
+    assert!(mutated.0.iter().all(|l| l.sigma > 0.));
+    assert!(brain
+        .0
+        .iter()
+        .zip(mutated.0.iter())
+        .all(|(a, b)| a.sigma != b.sigma));
+    assert!(changed(&brain, &mutated) > 0);
+}
+
+#[test]
+fn schedule_lowers_strength_on_plateau() {
+    let mut schedule = MutationSchedule::new(2, 0.5);
+    assert_eq!(schedule.update(10.), 1.);
+    assert_eq!(schedule.update(10.), 1.);
+    assert_eq!(schedule.update(9.), 0.5);
+    assert_eq!(schedule.update(11.), 0.5);
+    assert_eq!(schedule.update(11.), 0.5);
+    assert_eq!(schedule.update(11.), 0.25);
+}
+
+#[test]
+fn schedule_follows_updated_config() {
+    use wasm_self_driving_car::{Config, Simulation};
+
+    seed_random(9);
+    let config = Config {
+        cars_count: 5,
+        ..Config::default()
+    };
+    let mut simulation = Simulation::headless(&config);
+    simulation.update_config(&Config {
+        plateau_patience: 1,
+        plateau_factor: 0.5,
+        ..config
+    });
+
+    // agents do not move, so the second generation is no better than the first one
+    assert_eq!(simulation.next_generation().unwrap().mutation_scale, 1.);
+    assert_eq!(simulation.next_generation().unwrap().mutation_scale, 0.5);
+}
@@ -0,0 +1,109 @@
+//! Native tests of the NEAT genome
+
+use wasm_self_driving_car::ai::neat::{Genome, InnovationTracker, Neat, NeatSettings};
+use wasm_self_driving_car::utils::seed_random;
+
+#[test]
+fn new_genome_connects_every_input_to_every_output() {
+    seed_random(1);
+    let mut tracker = InnovationTracker::new(3, 2);
+    let genome = Genome::new(3, 2, &mut tracker);
+
+    assert_eq!(genome.nodes.len(), 5);
+    assert_eq!(genome.connections.len(), 6);
+    assert_eq!(genome.activate(&[0.1, 0.5, 1.]).len(), 2);
+}
+
+#[test]
+fn same_structural_mutation_gets_same_innovation() {
+    seed_random(2);
+    let mut tracker = InnovationTracker::new(2, 1);
+    let first = Genome::new(2, 1, &mut tracker);
+    let second = Genome::new(2, 1, &mut tracker);
+
+    let innovations = |g: &Genome| {
+        g.connections
+            .iter()
+            .map(|c| c.innovation)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(innovations(&first), innovations(&second));
+    assert_eq!(
+        first.compatibility_distance(&first, &NeatSettings::default()),
+        0.
+    );
+}
+
+#[test]
+fn add_node_splits_connection() {
+    seed_random(3);
+    let mut tracker = InnovationTracker::new(2, 1);
+    let mut genome = Genome::new(2, 1, &mut tracker);
+    genome.add_node(&mut tracker);
+
+    assert_eq!(genome.nodes.len(), 4);
+    assert_eq!(genome.connections.iter().filter(|c| !c.enabled).count(), 1);
+    assert_eq!(genome.connections.iter().filter(|c| c.enabled).count(), 3);
+    // hidden node is evaluated before the output it feeds
+    assert_eq!(genome.topological_order().len(), 4);
+}
+
+#[test]
+fn crossover_keeps_structure_of_fitter_parent() {
+    seed_random(4);
+    let mut tracker = InnovationTracker::new(2, 1);
+    let mut fitter = Genome::new(2, 1, &mut tracker);
+    let other = Genome::new(2, 1, &mut tracker);
+    fitter.add_node(&mut tracker);
+
+    let child = Genome::crossover(&fitter, &other);
+    assert_eq!(child.nodes.len(), fitter.nodes.len());
+    assert_eq!(child.connections.len(), fitter.connections.len());
+}
+
+#[test]
+fn evolution_keeps_population_size() {
+    seed_random(5);
+    let mut neat = Neat::new(2, 1, 30, NeatSettings::default());
+    for _ in 0..10 {
+        let fitness = neat
+            .genomes
+            .iter()
+            .map(|g| g.activate(&[1., 0.])[0] - g.activate(&[0., 1.])[0])
+            .collect::<Vec<f64>>();
+        neat.evolve(&fitness);
+        assert_eq!(neat.genomes.len(), 30);
+        assert!(!neat.species.is_empty());
+    }
+    assert_eq!(neat.generation, 10);
+}
+
+#[test]
+fn hidden_nodes_are_laid_out_between_inputs_and_outputs() {
+    seed_random(6);
+    let mut tracker = InnovationTracker::new(2, 1);
+    let mut genome = Genome::new(2, 1, &mut tracker);
+    let levels = genome.levels();
+    assert_eq!(levels.len(), 2);
+    assert_eq!(levels[0].len(), 2);
+    assert_eq!(levels[1].len(), 1);
+
+    genome.add_node(&mut tracker);
+    let hidden = genome.nodes.last().unwrap().id;
+    assert_eq!(
+        genome.levels(),
+        vec![levels[0].clone(), vec![hidden], levels[1].clone()]
+    );
+
+    // crossover can disable the only connection into the hidden node, it still gets its own level
+    genome
+        .connections
+        .iter_mut()
+        .filter(|c| c.to == hidden)
+        .for_each(|c| c.enabled = false);
+    assert_eq!(genome.depths()[&hidden], 0);
+    assert_eq!(
+        genome.levels(),
+        vec![levels[0].clone(), vec![hidden], levels[1].clone()]
+    );
+}
@@ -0,0 +1,226 @@
+//! Native tests of the neural network inference and its storage format
+
+use serde_json::Value;
+use wasm_self_driving_car::ai::NeuralNetwork;
+use wasm_self_driving_car::utils::seed_random;
+
+/// Brains saved before weights were flattened, stored as `weights[input][output]`
+const LEGACY_SAVES: &str = include_str!("../successful_brains.json");
+
+/// Feed forward the way it was computed before weights were flattened
+fn legacy_forward(levels: &[Value], inputs: &[f64]) -> Vec<f64> {
+    levels.iter().fold(inputs.to_vec(), |inputs, level| {
+        let weights = level["weights"].as_array().unwrap();
+        let biases = level["biases"].as_array().unwrap();
+        (0..biases.len())
+            .map(|output| {
+                let sum = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(input, value)| value * weights[input][output].as_f64().unwrap())
+                    .sum::<f64>();
+                ((sum + biases[output].as_f64().unwrap() > 0.) as u8) as f64
+            })
+            .collect()
+    })
+}
+
+#[test]
+fn legacy_saves_give_same_outputs() {
+    let saves: Value = serde_json::from_str(LEGACY_SAVES).unwrap();
+    for save in saves["brains"].as_array().unwrap() {
+        let levels = save["brain"].as_array().unwrap();
+        let mut network = NeuralNetwork::deserialize_brain(save["brain"].to_string())
+            .expect("failed to load legacy brain");
+
+        for step in 0..20 {
+            let inputs = (0..levels[0]["inputs"].as_array().unwrap().len())
+                .map(|i| ((step * 7 + i) % 10) as f64 / 10.)
+                .collect::<Vec<f64>>();
+            let expected = legacy_forward(levels, &inputs);
+            assert_eq!(network.forward(&inputs), expected);
+            assert_eq!(network.feed_forward(&inputs), expected.as_slice());
+        }
+    }
+}
+
+#[test]
+fn flat_format_round_trips() {
+    seed_random(1);
+    let network = NeuralNetwork::new(&[5, 6, 4]);
+    let loaded = NeuralNetwork::deserialize_brain(network.serialize_brain()).unwrap();
+
+    // serde_json parsing of floats is not guaranteed to be exact to the last bit
+    assert!(network
+        .parameters()
+        .zip(loaded.parameters())
+        .all(|(a, b)| (a - b).abs() < 1e-12));
+    assert_eq!(network.parameters_count(), loaded.parameters_count());
+    assert_eq!(loaded.0[0].inputs.len(), 5);
+    assert_eq!(loaded.0[1].outputs.len(), 4);
+}
+
+#[test]
+fn levels_of_inconsistent_shape_are_rejected() {
+    let brain =
+        |level: Value| NeuralNetwork::deserialize_brain(Value::from(vec![level]).to_string());
+    let level =
+        serde_json::json!({ "input_count": 2, "biases": [0., 0.], "weights": [1., 2., 3., 4.] });
+    assert!(brain(level.clone()).is_some());
+
+    let mut short = level.clone();
+    short["weights"] = serde_json::json!([1., 2., 3.]);
+    assert!(brain(short).is_none());
+
+    // legacy row shorter than the number of outputs
+    let legacy =
+        serde_json::json!({ "inputs": [0., 0.], "biases": [0., 0.], "weights": [[1., 2.], [3.]] });
+    assert!(brain(legacy).is_none());
+
+    let mut recurrent = level.clone();
+    recurrent["recurrent"] = serde_json::json!([1., 2., 3.]);
+    assert!(brain(recurrent.clone()).is_none());
+    recurrent["recurrent"] = serde_json::json!([1., 2., 3., 4.]);
+    assert_eq!(brain(recurrent).unwrap().0[0].memory, vec![0., 0.]);
+
+    let mut empty = level;
+    empty["input_count"] = 0.into();
+    empty["weights"] = serde_json::json!([]);
+    assert!(brain(empty).is_none());
+}
+
+#[test]
+fn weights_are_row_major() {
+    seed_random(2);
+    let network = NeuralNetwork::new(&[3, 2]);
+    let level = &network.0[0];
+
+    assert_eq!(level.weights.len(), 6);
+    assert_eq!(level.weight(2, 1), level.weights[5]);
+    assert_eq!(level.weight(0, 1), level.weights[3]);
+}
+
+#[test]
+fn recurrent_brain_remembers_and_survives_save() {
+    use wasm_self_driving_car::ai::mutation::Mutation;
+
+    seed_random(9);
+    let mut brain = NeuralNetwork::new(&[3, 6, 4])
+        .with_activation(wasm_self_driving_car::ai::Activation::Sigmoid)
+        .with_memory();
+    assert!(brain.is_recurrent());
+    assert!(brain.0[0].is_recurrent());
+    assert!(!brain.0[1].is_recurrent());
+    assert_eq!(brain.parameters_count(), 3 * 6 + 6 + 6 * 6 + 6 * 4 + 4);
+
+    // same inputs give different outputs, because the hidden level remembers the previous tick
+    let first = brain.feed_forward(&[1., 0., 1.]).to_vec();
+    let second = brain.feed_forward(&[1., 0., 1.]).to_vec();
+    assert_ne!(first, second);
+
+    brain.reset_memory();
+    assert_eq!(brain.feed_forward(&[1., 0., 1.]), first.as_slice());
+
+    // JSON keeps the memory too, the compact format only the weights
+    let mut loaded = NeuralNetwork::deserialize_brain(brain.serialize_brain()).unwrap();
+    assert_eq!(loaded.0[0].recurrent.len(), 36);
+    assert_eq!(loaded.feed_forward(&[1., 0., 1.]), second.as_slice());
+
+    let mut loaded = NeuralNetwork::from_base64(&brain.to_base64()).unwrap();
+    assert_eq!(loaded.feed_forward(&[1., 0., 1.]), first.as_slice());
+
+    let mutated = brain.mutate_with(&Mutation::lerp(1.));
+    assert_ne!(mutated.0[0].recurrent, brain.0[0].recurrent);
+}
+
+#[test]
+fn levels_record_their_last_inputs() {
+    seed_random(3);
+    let mut brain = NeuralNetwork::new(&[3, 5, 4]);
+    let outputs = brain.feed_forward(&[0.5, 0., 1.]).to_vec();
+
+    assert_eq!(brain.0[0].inputs, vec![0.5, 0., 1.]);
+    assert_eq!(brain.0[1].inputs, brain.0[0].outputs);
+
+    let activations = brain.level_activations();
+    assert_eq!(activations.len(), 3);
+    assert_eq!(activations[0], &[0.5, 0., 1.]);
+    assert_eq!(activations[1], brain.0[0].outputs.as_slice());
+    assert_eq!(activations[2], outputs.as_slice());
+}
+
+#[test]
+fn compact_format_round_trip() {
+    use wasm_self_driving_car::ai::format::{DecodeError, FORMAT_VERSION};
+
+    seed_random(4);
+    let brain = NeuralNetwork::new(&[5, 6, 4]).with_memory();
+    let bytes = brain.to_bytes();
+    assert_eq!(&bytes[..4], b"SDCB");
+    assert_eq!(bytes[4], FORMAT_VERSION);
+
+    let decoded = NeuralNetwork::from_bytes(&bytes).unwrap();
+    assert!(decoded.0[0].is_recurrent());
+    assert_eq!(
+        decoded.parameters().copied().collect::<Vec<f64>>(),
+        brain.parameters().copied().collect::<Vec<f64>>()
+    );
+    assert_eq!(decoded.0[1].activation, brain.0[1].activation);
+    assert_eq!(decoded.to_base64(), brain.to_base64());
+
+    // JSON is not exact to the last bit, see `flat_format_round_trips`
+    let json = NeuralNetwork::compact_to_json(brain.to_base64()).unwrap();
+    let converted = NeuralNetwork::json_to_compact(json).unwrap();
+    assert!(NeuralNetwork::from_base64(&converted)
+        .unwrap()
+        .parameters()
+        .zip(brain.parameters())
+        .all(|(a, b)| (a - b).abs() < 1e-12));
+
+    assert_eq!(
+        NeuralNetwork::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+        DecodeError::Truncated
+    );
+    let mut newer = bytes.clone();
+    newer[4] = FORMAT_VERSION + 1;
+    assert_eq!(
+        NeuralNetwork::from_bytes(&newer).unwrap_err(),
+        DecodeError::UnsupportedVersion(FORMAT_VERSION + 1)
+    );
+    assert_eq!(
+        NeuralNetwork::from_base64("e30=").unwrap_err(),
+        DecodeError::NotBrain
+    );
+
+    // input count of the first level is right after the header
+    let mut empty = bytes.clone();
+    empty[9..13].copy_from_slice(&0u32.to_le_bytes());
+    assert_eq!(
+        NeuralNetwork::from_bytes(&empty).unwrap_err(),
+        DecodeError::LevelSize
+    );
+    let mut huge = bytes.clone();
+    huge[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+    huge[13..17].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(NeuralNetwork::from_bytes(&huge).is_err());
+}
+
+#[test]
+fn legacy_saves_migrate_to_compact_format() {
+    use wasm_self_driving_car::ai::population::HallOfFameEntry;
+
+    let saves: Value = serde_json::from_str(LEGACY_SAVES).unwrap();
+    for save in saves["brains"].as_array().unwrap() {
+        let legacy = serde_json::json!({ "brain": save["brain"], "fitness": 1., "generation": 2 });
+        let entry: HallOfFameEntry = serde_json::from_value(legacy.clone()).unwrap();
+
+        let migrated = serde_json::to_string(&entry).unwrap();
+        assert!(migrated.len() < legacy.to_string().len());
+
+        let loaded: HallOfFameEntry = serde_json::from_str(&migrated).unwrap();
+        assert_eq!(
+            loaded.brain.parameters().copied().collect::<Vec<f64>>(),
+            entry.brain.parameters().copied().collect::<Vec<f64>>()
+        );
+    }
+}
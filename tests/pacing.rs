@@ -0,0 +1,60 @@
+//! Native tests of running several ticks per frame
+
+use wasm_self_driving_car::pacing::Pacing;
+use wasm_self_driving_car::{Config, Simulation};
+
+#[test]
+fn speed_decides_ticks_per_frame() {
+    let mut pacing = Pacing::default();
+    assert_eq!(pacing.ticks_for_frame(), 1);
+
+    pacing.set_speed(0.5);
+    let ticks = (0..4)
+        .map(|_| pacing.ticks_for_frame())
+        .collect::<Vec<u32>>();
+    assert_eq!(ticks, vec![0, 1, 0, 1]);
+
+    pacing.set_speed(2.5);
+    assert_eq!(pacing.ticks_for_frame() + pacing.ticks_for_frame(), 5);
+
+    pacing.set_speed(-1.);
+    assert_eq!(pacing.ticks_for_frame(), 0);
+
+    pacing.set_turbo_budget(Some(0.));
+    assert_eq!(pacing.turbo_budget(), None);
+    pacing.set_turbo_budget(Some(12.));
+    assert_eq!(pacing.turbo_budget(), Some(12.));
+}
+
+#[test]
+fn ticks_per_second_are_measured_over_a_second() {
+    let mut pacing = Pacing::default();
+    pacing.measure(10, 0.);
+    pacing.measure(100, 500.);
+    assert_eq!(pacing.ticks_per_second(), 0.);
+
+    pacing.measure(90, 1000.);
+    assert_eq!(pacing.ticks_per_second(), 200.);
+
+    // new window starts where the previous one ended
+    pacing.measure(30, 1500.);
+    pacing.measure(30, 2000.);
+    assert_eq!(pacing.ticks_per_second(), 60.);
+}
+
+#[test]
+fn simulation_runs_several_ticks_at_once() {
+    let mut simulation = Simulation::headless(&Config::default());
+    assert_eq!(simulation.update_n(10), 0);
+
+    simulation.run();
+    assert_eq!(simulation.update_n(10), 10);
+    assert_eq!(simulation.tick(), 10);
+
+    let ticks = simulation.update_for(5.);
+    assert!(ticks >= 1);
+    assert_eq!(simulation.tick(), 10 + ticks as u64);
+
+    simulation.pause();
+    assert_eq!(simulation.update_for(5.), 0);
+}
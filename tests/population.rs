@@ -0,0 +1,198 @@
+//! Native tests of the population diversity tools
+
+use wasm_self_driving_car::ai::{
+    mutation::Mutation,
+    population::{
+        breed, cluster, diversity, shared_fitness, weight_distance, Breeding, HallOfFame,
+        HallOfFameEntry, NoveltyArchive, Selection,
+    },
+    NeuralNetwork,
+};
+use wasm_self_driving_car::utils::seed_random;
+
+#[test]
+fn identical_brains_have_no_distance() {
+    seed_random(1);
+    let brain = NeuralNetwork::new(&[3, 4, 2]);
+
+    assert_eq!(weight_distance(&brain, &brain), 0.);
+    assert!(weight_distance(&brain, &brain.mutate(0.5)) > 0.);
+    assert_eq!(
+        weight_distance(&brain, &NeuralNetwork::new(&[3, 2])),
+        f64::INFINITY
+    );
+    assert_eq!(diversity(&[&brain, &brain, &brain]), 0.);
+}
+
+#[test]
+fn close_brains_share_species() {
+    seed_random(2);
+    let first = NeuralNetwork::new(&[3, 4, 2]);
+    let second = NeuralNetwork::new(&[3, 4, 2]);
+    let brains = [&first, &first.mutate(0.01), &second, &second.mutate(0.01)];
+
+    let species = cluster(&brains, 0.1);
+    assert_eq!(species, vec![vec![0, 1], vec![2, 3]]);
+
+    let shared = shared_fitness(&[10., 10., 10., 0.], &species);
+    assert_eq!(shared, vec![5.5, 5.5, 5.5, 0.5]);
+}
+
+#[test]
+fn breeding_keeps_population_size_and_elites() {
+    seed_random(3);
+    let first = NeuralNetwork::new(&[3, 2]);
+    let second = NeuralNetwork::new(&[3, 2]);
+    let brains = [&first, &second];
+    let breeding = Breeding {
+        selection: Selection::Truncation,
+        selection_size: 1,
+        elitism: 2,
+        mutation: Mutation::lerp(0.1),
+    };
+
+    let children = breed(&brains, &[50., 50.], &[vec![0], vec![1]], 10, &breeding);
+    assert_eq!(children.len(), 10);
+    assert!(children.iter().any(|c| weight_distance(c, &first) == 0.));
+    assert!(children.iter().any(|c| weight_distance(c, &second) == 0.));
+}
+
+#[test]
+fn novelty_rewards_only_unvisited_states() {
+    let mut archive = NoveltyArchive::new(100.);
+    archive.visit(0, 1, 50.);
+    archive.visit(0, 1, 60.);
+    archive.visit(0, 1, 150.);
+    archive.visit(1, 1, 50.);
+    assert_eq!(archive.score(0), 2.);
+    assert_eq!(archive.score(1), 1.);
+    assert_eq!(archive.end_generation(), 2);
+
+    archive.visit(0, 1, 50.);
+    archive.visit(0, 2, 50.);
+    assert_eq!(archive.score(0), 1.);
+}
+
+#[test]
+fn selection_prefers_better_brains() {
+    seed_random(4);
+    let scores = [100., 50., 10., 0.];
+    let candidates = [0, 1, 2, 3];
+
+    for _ in 0..20 {
+        assert_eq!(Selection::Truncation.select(&scores, &candidates, 1), 0);
+        assert!(Selection::Truncation.select(&scores, &candidates, 2) < 2);
+        assert_eq!(Selection::Tournament.select(&scores, &[3, 2], 30), 2);
+    }
+
+    let picks = |selection: Selection| {
+        (0..1000)
+            .filter(|_| selection.select(&scores, &candidates, 2) == 0)
+            .count()
+    };
+    assert!(picks(Selection::Roulette) > picks(Selection::Rank) / 2);
+    assert!(picks(Selection::Rank) > 250);
+}
+
+#[test]
+fn hall_of_fame_keeps_best_brains_ordered() {
+    seed_random(5);
+    let mut hall = HallOfFame::default();
+    for (generation, fitness) in [10., 30., 20., 5.].iter().enumerate() {
+        hall.submit(
+            HallOfFameEntry {
+                brain: NeuralNetwork::new(&[2, 1]),
+                fitness: *fitness,
+                generation,
+            },
+            2,
+        );
+    }
+
+    let fitness = hall.entries.iter().map(|e| e.fitness).collect::<Vec<f64>>();
+    assert_eq!(fitness, vec![30., 20.]);
+    assert_eq!(hall.best().map(|e| e.generation), Some(1));
+}
+
+#[test]
+fn generation_stats_summarize_fitness() {
+    use wasm_self_driving_car::ai::population::GenerationStats;
+
+    let stats = GenerationStats::from_fitness(3, &[4., -1., 10., 2.]);
+    assert_eq!(stats.generation, 3);
+    assert_eq!(stats.best_fitness, 10.);
+    assert_eq!(stats.worst_fitness, -1.);
+    assert_eq!(stats.mean_fitness, 3.75);
+    assert_eq!(stats.median_fitness, 3.);
+    assert_eq!(
+        GenerationStats::from_fitness(0, &[5., 1., 3.]).median_fitness,
+        3.
+    );
+
+    let csv = GenerationStats::to_csv(&[stats, stats]);
+    let lines = csv.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], GenerationStats::CSV_HEADER);
+    assert_eq!(lines[1].split(',').count(), lines[0].split(',').count());
+    assert!(lines[1].starts_with("3,10,3.75,3,-1,"));
+}
+
+#[test]
+fn simulation_keeps_generation_history() {
+    use wasm_self_driving_car::{Config, Simulation};
+
+    seed_random(6);
+    let config = Config {
+        cars_count: 20,
+        ..Config::default()
+    };
+    let mut simulation = Simulation::headless(&config);
+    for _ in 0..2 {
+        simulation.training_traffic();
+        simulation.run();
+        for _ in 0..300 {
+            simulation.update();
+        }
+        simulation.next_generation().unwrap();
+    }
+
+    let history = simulation.history();
+    assert_eq!(history.len(), 2);
+    for (generation, stats) in history.iter().enumerate() {
+        assert_eq!(stats.generation, generation);
+        assert!(stats.worst_fitness <= stats.median_fitness);
+        assert!(stats.median_fitness <= stats.best_fitness);
+        assert!(stats.survivors <= config.cars_count);
+        assert!(stats.wall_time >= 0.);
+    }
+
+    assert_eq!(simulation.history_csv().lines().count(), 3);
+    let json: serde_json::Value = serde_json::from_str(&simulation.history_json()).unwrap();
+    assert_eq!(json[1]["bestFitness"], history[1].best_fitness);
+}
+
+#[test]
+fn agents_behind_are_retired_with_their_score() {
+    use wasm_self_driving_car::ai::agents::Agents;
+    use wasm_self_driving_car::car::Car;
+    use wasm_self_driving_car::{Config, CAR_Y_DEFAULT};
+
+    let config = Config {
+        cars_count: 3,
+        ..Config::default()
+    };
+    let mut cars = Car::generate_cars_same(100., None, &config);
+    cars.iter_mut().for_each(|car| car.damaged = true);
+    cars[0].y = -1000.;
+    let ids = cars.iter().map(|car| car.id).collect::<Vec<usize>>();
+
+    let mut agents = Agents::new(cars);
+    agents.clean();
+
+    assert_eq!(agents.population().count(), 1);
+    assert_eq!(
+        agents.retired(),
+        &[(ids[1], -CAR_Y_DEFAULT), (ids[2], -CAR_Y_DEFAULT)]
+    );
+    assert!(agents.get(ids[1]).is_none());
+}
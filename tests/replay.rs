@@ -0,0 +1,135 @@
+//! Native tests of recording replays and playing them back
+
+use wasm_self_driving_car::car::Car;
+use wasm_self_driving_car::replay::{CarKind, ReplayFile, ReplayPlayer, REPLAY_VERSION};
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::{Config, Simulation};
+
+fn recorded(ticks: usize) -> String {
+    seed_random(11);
+    let mut simulation = Simulation::headless(&Config {
+        cars_count: 20,
+        ..Config::default()
+    });
+    simulation.training_traffic();
+    simulation.run();
+    simulation.start_replay();
+    for _ in 0..ticks {
+        simulation.update();
+    }
+    simulation.stop_replay();
+    simulation.update();
+
+    assert_eq!(simulation.replay_frames_count(), ticks + 1);
+    simulation.export_replay().unwrap()
+}
+
+#[test]
+fn replay_round_trips_through_file() {
+    let json = recorded(100);
+    let file = ReplayFile::from_json(&json).unwrap();
+    let frames = &file.replay.frames;
+    assert_eq!(frames.len(), 101);
+    assert!(file.save.is_some());
+
+    let last = frames.last().unwrap();
+    assert!(last.cars.iter().any(|pose| pose.kind == CarKind::Agent));
+    assert!(last
+        .cars
+        .iter()
+        .any(|pose| matches!(pose.kind, CarKind::Traffic(_))));
+    // agents drive forward, so they end up above where they started
+    let start = frames[0]
+        .cars
+        .iter()
+        .find(|p| p.kind == CarKind::Agent)
+        .unwrap();
+    let end = last.cars.iter().find(|p| p.id == start.id).unwrap();
+    assert!(end.y < start.y);
+
+    // car drawn from the pose is where the recorded one was
+    let car = Car::from_pose(end);
+    assert_eq!(car.y, end.y as f64);
+    assert_eq!(car.damaged, end.damaged());
+    assert_eq!(Car::from_pose(end).pose(), *end);
+
+    assert_eq!(
+        ReplayFile::from_json(&file.to_json())
+            .unwrap()
+            .replay
+            .frames,
+        *frames
+    );
+}
+
+#[test]
+fn corrupted_replay_is_rejected() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use wasm_self_driving_car::replay::Replay;
+
+    let file = ReplayFile::from_json(&recorded(5)).unwrap();
+    let bytes = Replay::frames_to_bytes(&file.replay.frames);
+    assert_eq!(&bytes[..4], b"SDCR");
+    assert_eq!(bytes[4], REPLAY_VERSION);
+
+    assert!(Replay::frames_from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    let mut newer = bytes.clone();
+    newer[4] = REPLAY_VERSION + 1;
+    assert!(Replay::frames_from_bytes(&newer)
+        .unwrap_err()
+        .contains("not supported"));
+
+    let mut json: serde_json::Value = serde_json::from_str(&file.to_json()).unwrap();
+    json["replay"]["frames"] = STANDARD.encode(&bytes[..20]).into();
+    assert!(ReplayFile::from_json(&json.to_string()).is_err());
+}
+
+#[test]
+fn player_scrubs_pauses_and_changes_speed() {
+    let file = ReplayFile::from_json(&recorded(10)).unwrap();
+    let mut player = ReplayPlayer::new(file.clone());
+    assert_eq!(player.frames_count(), 11);
+
+    // paused player stays on the frame
+    player.advance();
+    assert_eq!(player.position(), 0);
+
+    player.play();
+    player.set_speed(0.5);
+    player.advance();
+    assert_eq!(player.position(), 0);
+    player.advance();
+    assert_eq!(player.position(), 1);
+
+    player.set_speed(4.);
+    player.advance();
+    assert_eq!(player.position(), 5);
+
+    player.seek(100);
+    assert_eq!(player.position(), 10);
+    player.advance();
+    assert!(!player.is_playing());
+
+    // playing finished replay starts it from the beginning
+    player.play();
+    assert_eq!(player.position(), 0);
+    player.pause();
+    player.seek(3);
+    player.advance();
+    assert_eq!(player.position(), 3);
+    assert_eq!(player.frame(), Some(&file.replay.frames[3]));
+}
+
+#[test]
+fn recording_stops_when_replay_is_full() {
+    use wasm_self_driving_car::ai::agents::Agents;
+    use wasm_self_driving_car::replay::{Replay, MAX_REPLAY_FRAMES};
+    use wasm_self_driving_car::road::Road;
+    use wasm_self_driving_car::traffic::Traffic;
+
+    let mut replay = Replay::new(Road::new(0., 100., 3));
+    let (traffic, agents) = (Traffic::new(), Agents::new(vec![]));
+    assert!((0..MAX_REPLAY_FRAMES).all(|_| replay.record(&traffic, &agents)));
+    assert!(!replay.record(&traffic, &agents));
+    assert_eq!(replay.frames.len(), MAX_REPLAY_FRAMES);
+}
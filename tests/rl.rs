@@ -0,0 +1,78 @@
+//! Native tests of the reinforcement learning trainer
+
+use wasm_self_driving_car::ai::rl::{discounted_returns, ReinforceTrainer};
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::{Config, Simulation};
+
+#[test]
+fn returns_are_discounted_from_the_end() {
+    let returns = discounted_returns(&[1., 1., 1.], 0.5);
+    assert_eq!(returns, vec![1.75, 1.5, 1.]);
+}
+
+#[test]
+fn episode_runs_without_browser() {
+    seed_random(11);
+    let mut trainer = ReinforceTrainer::new(&Config::default(), 0.01);
+    trainer.max_ticks = 200;
+
+    let before = trainer.policy();
+    let stats = trainer.train_episode();
+
+    assert!(stats.ticks > 0 && stats.ticks <= 200);
+    assert_eq!(trainer.episodes(), 1);
+    // policy was pushed towards the better actions of the episode
+    let after = trainer.policy();
+    assert!(before
+        .parameters()
+        .zip(after.parameters())
+        .any(|(before, after)| before != after));
+}
+
+#[test]
+fn simulation_trainer_follows_config() {
+    seed_random(11);
+    let config = Config {
+        cars_count: 2,
+        ..Config::default()
+    };
+    let mut simulation = Simulation::headless(&config);
+    simulation.train_reinforce(1, 0.01);
+
+    let rays_count = config.rays_count + 2;
+    simulation.update_config(&Config {
+        rays_count,
+        ..config
+    });
+    simulation.train_reinforce(1, 0.01);
+
+    // restarted agents got brain fitting their sensors
+    let agents = simulation.capture_snapshot().agents;
+    assert!(agents
+        .iter()
+        .all(|car| car.brain().unwrap().0[0].input_count() == rays_count));
+}
+
+#[test]
+fn standing_agent_stalls_like_in_scenario_evaluation() {
+    use wasm_self_driving_car::ai::NeuralNetwork;
+    use wasm_self_driving_car::scenario::STALL_TICKS;
+
+    seed_random(12);
+    let config = Config::default();
+    let mut policy = NeuralNetwork::trainable(&config.neurons_count());
+    // outputs are close to 0, so the agent never presses anything
+    policy
+        .0
+        .last_mut()
+        .unwrap()
+        .biases
+        .iter_mut()
+        .for_each(|bias| *bias = -100.);
+
+    let mut trainer = ReinforceTrainer::new(&config, 0.01);
+    trainer.set_policy(&policy).unwrap();
+    let stats = trainer.evaluate(0);
+    assert!(!stats.crashed && !stats.finished);
+    assert_eq!(stats.ticks, STALL_TICKS + 1);
+}
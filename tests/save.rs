@@ -0,0 +1,92 @@
+//! Native tests of saves shared as files
+
+use wasm_self_driving_car::ai::NeuralNetwork;
+use wasm_self_driving_car::save::Save;
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::Config;
+
+fn save() -> Save {
+    let config = Config::default();
+    Save {
+        brain: NeuralNetwork::from_config(&config),
+        config,
+        hall_of_fame: Default::default(),
+    }
+}
+
+#[test]
+fn exported_save_imports() {
+    seed_random(1);
+    let save = save();
+    let imported = Save::from_json(&save.to_json()).unwrap();
+
+    assert_eq!(imported.to_json(), save.to_json());
+    assert_eq!(imported.config.cars_count, save.config.cars_count);
+}
+
+#[test]
+fn invalid_saves_are_rejected() {
+    seed_random(2);
+    assert!(Save::from_json("not a save").is_err());
+    assert!(Save::from_json("{}").is_err());
+
+    let mut save = self::save();
+    save.config.rays_count += 1;
+    let error = Save::from_json(&save.to_json()).unwrap_err();
+    assert!(error.contains("inputs"), "{}", error);
+
+    let mut save = self::save();
+    save.config.lane_index = save.config.lanes_count;
+    assert!(Save::from_json(&save.to_json()).is_err());
+
+    let mut save = self::save();
+    save.brain.0[0].weights[0] = f64::NAN;
+    let error = Save::from_json(&save.to_json()).unwrap_err();
+    assert!(error.contains("finite"), "{}", error);
+
+    let mut save = self::save();
+    save.brain = NeuralNetwork::new(&[save.config.rays_count, 3]);
+    assert!(Save::from_json(&save.to_json()).is_err());
+
+    let mut save = self::save();
+    save.config.rays_count = 0;
+    save.brain = NeuralNetwork::new(&[0, 4]);
+    let error = save.validate().unwrap_err();
+    assert!(error.contains("rays"), "{}", error);
+}
+
+#[test]
+fn brains_of_inconsistent_shape_are_rejected() {
+    seed_random(3);
+    let mut save = self::save();
+    save.brain.0[0].weights.pop();
+    let error = save.validate().unwrap_err();
+    assert!(error.contains("level 1"), "{}", error);
+
+    // levels which do not chain together
+    let mut save = self::save();
+    let rays_count = save.config.rays_count;
+    save.brain = NeuralNetwork::new(&[rays_count, 6, 4]);
+    save.brain.0[1] = NeuralNetwork::new(&[5, 4]).0.remove(0);
+    let error = save.validate().unwrap_err();
+    assert!(error.contains("previous level"), "{}", error);
+
+    let mut save = self::save();
+    save.brain = NeuralNetwork::new(&[rays_count, 6, 4]).with_memory();
+    assert!(save.validate().is_ok());
+    save.brain.0[0].memory.pop();
+    let error = save.validate().unwrap_err();
+    assert!(error.contains("recurrent"), "{}", error);
+}
+
+#[test]
+fn brains_with_empty_levels_are_rejected() {
+    seed_random(4);
+    let mut save = self::save();
+    let rays_count = save.config.rays_count;
+    save.brain = NeuralNetwork::new(&[rays_count, 6, 4]);
+    save.brain.0[1] = NeuralNetwork::new(&[0, 4]).0.remove(0);
+    save.brain.0[0] = NeuralNetwork::new(&[rays_count, 0]).0.remove(0);
+    let error = save.validate().unwrap_err();
+    assert!(error.contains("at least one"), "{}", error);
+}
@@ -0,0 +1,110 @@
+//! Native tests of capturing and restoring the simulated world
+
+use serde_json::Value;
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::{Config, Simulation};
+
+fn world(simulation: &Simulation) -> Value {
+    serde_json::from_str(&simulation.snapshot()).unwrap()
+}
+
+#[test]
+fn restored_world_continues_identically() {
+    seed_random(7);
+    let config = Config {
+        cars_count: 30,
+        recurrent: true,
+        ..Config::default()
+    };
+    let mut simulation = Simulation::headless(&config);
+    simulation.training_traffic();
+    simulation.run();
+    for _ in 0..50 {
+        simulation.update();
+    }
+
+    let snapshot = simulation.snapshot();
+    for _ in 0..200 {
+        simulation.update();
+    }
+
+    let mut restored = Simulation::headless(&Config::default());
+    restored.run();
+    assert!(restored.restore(&snapshot));
+    assert_eq!(restored.tick(), 50);
+    for _ in 0..200 {
+        restored.update();
+    }
+
+    assert_eq!(restored.tick(), 250);
+    assert_eq!(world(&restored), world(&simulation));
+}
+
+#[test]
+fn paused_simulation_does_not_tick() {
+    let mut simulation = Simulation::headless(&Config::default());
+    simulation.update();
+    assert_eq!(simulation.tick(), 0);
+
+    simulation.run();
+    simulation.update();
+    simulation.pause();
+    simulation.update();
+    assert_eq!(simulation.tick(), 1);
+}
+
+#[test]
+fn rewound_world_matches_the_past() {
+    seed_random(8);
+    let mut simulation = Simulation::headless(&Config {
+        cars_count: 30,
+        ..Config::default()
+    });
+    simulation.training_traffic();
+    simulation.run();
+    for _ in 0..145 {
+        simulation.update();
+    }
+    let past = world(&simulation);
+    simulation.update();
+    let next = world(&simulation);
+    for _ in 0..54 {
+        simulation.update();
+    }
+    assert_eq!(simulation.tick(), 200);
+
+    assert_eq!(simulation.rewind(55), 55);
+    assert_eq!(simulation.tick(), 145);
+    assert_eq!(world(&simulation), past);
+
+    // stepping works only while paused, which rewinding does
+    assert!(simulation.step_forward());
+    assert_eq!(world(&simulation), next);
+    assert!(simulation.step_back());
+    assert_eq!(world(&simulation), past);
+
+    simulation.run();
+    assert!(!simulation.step_forward());
+    assert!(!simulation.step_back());
+    assert_eq!(simulation.tick(), 145);
+}
+
+#[test]
+fn rewind_stops_at_oldest_stored_tick() {
+    use wasm_self_driving_car::snapshot::{REWIND_CAPACITY, REWIND_INTERVAL};
+
+    let mut simulation = Simulation::headless(&Config::default());
+    assert_eq!(simulation.rewind(10), 0);
+
+    simulation.run();
+    let ticks = REWIND_INTERVAL * (REWIND_CAPACITY as u64 + 2) + 5;
+    for _ in 0..ticks {
+        simulation.update();
+    }
+
+    let oldest = REWIND_INTERVAL * 3;
+    assert_eq!(simulation.rewindable_ticks(), ticks - oldest);
+    assert_eq!(simulation.rewind(u32::MAX) as u64, ticks - oldest);
+    assert_eq!(simulation.tick(), oldest);
+    assert_eq!(simulation.rewind(1), 0);
+}
@@ -0,0 +1,70 @@
+//! Native tests of the storage using its in-memory implementation
+
+use std::{
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use wasm_self_driving_car::ai::NeuralNetwork;
+use wasm_self_driving_car::save::Save;
+use wasm_self_driving_car::storage::{
+    load_save, store_save, MemoryStorage, Storage, StorageError, Store,
+};
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::Config;
+
+/// In-memory storage never waits, so its futures are ready right away
+fn block_on<F: Future>(future: F) -> F::Output {
+    match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("in-memory storage should never be pending"),
+    }
+}
+
+#[test]
+fn stores_are_separate() {
+    let storage = MemoryStorage::new();
+    block_on(storage.set(Store::Brains, "b", "brain b")).unwrap();
+    block_on(storage.set(Store::Brains, "a", "brain a")).unwrap();
+    block_on(storage.set(Store::Datasets, "a", "dataset a")).unwrap();
+
+    assert_eq!(
+        block_on(storage.keys(Store::Brains)).unwrap(),
+        vec!["a".to_string(), "b".to_string()]
+    );
+    assert_eq!(
+        block_on(storage.get(Store::Datasets, "a")).unwrap(),
+        Some("dataset a".to_string())
+    );
+
+    block_on(storage.delete(Store::Brains, "a")).unwrap();
+    assert_eq!(block_on(storage.get(Store::Brains, "a")).unwrap(), None);
+    assert_eq!(
+        block_on(storage.get(Store::Datasets, "a")).unwrap(),
+        Some("dataset a".to_string())
+    );
+}
+
+#[test]
+fn saves_round_trip_and_are_validated() {
+    seed_random(1);
+    let config = Config::default();
+    let save = Save {
+        brain: NeuralNetwork::from_config(&config),
+        config,
+        hall_of_fame: Default::default(),
+    };
+
+    let storage = MemoryStorage::new();
+    block_on(store_save(&storage, "best", &save)).unwrap();
+    let loaded = block_on(load_save(&storage, "best")).unwrap().unwrap();
+    assert_eq!(loaded.to_json(), save.to_json());
+    assert!(block_on(load_save(&storage, "missing")).unwrap().is_none());
+
+    block_on(storage.set(Store::Brains, "broken", "{}")).unwrap();
+    assert!(matches!(
+        block_on(load_save(&storage, "broken")),
+        Err(StorageError::Invalid(_))
+    ));
+}
@@ -0,0 +1,99 @@
+//! Native tests of per-agent telemetry
+
+use std::collections::HashMap;
+
+use wasm_self_driving_car::ai::agents::Agents;
+use wasm_self_driving_car::car::Car;
+use wasm_self_driving_car::events::Event;
+use wasm_self_driving_car::road::Road;
+use wasm_self_driving_car::telemetry::Telemetry;
+use wasm_self_driving_car::traffic::Traffic;
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::vehicle::VehicleType;
+use wasm_self_driving_car::{Config, Simulation, CAR_CANVAS_WIDTH_DEFAULT};
+
+#[test]
+fn stats_follow_the_run() {
+    seed_random(12);
+    let config = Config {
+        cars_count: 50,
+        ..Config::default()
+    };
+    let mut simulation = Simulation::headless(&config);
+    simulation.training_traffic();
+    simulation.run();
+
+    let mut events = vec![];
+    for _ in 0..3000 {
+        simulation.update();
+        events.extend(simulation.drain_events());
+    }
+
+    let all = simulation.telemetry().all();
+    assert_eq!(all.len(), config.cars_count);
+    assert!(all.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+    let mut overtaken = HashMap::new();
+    for event in events.iter() {
+        match event {
+            Event::Crashed {
+                agent, cause, x, y, ..
+            } => {
+                let stats = simulation.agent_stats(*agent).unwrap();
+                assert_eq!(stats.crash, Some(*cause));
+                assert_eq!(stats.crash_location, Some([*x, *y]));
+            }
+            Event::Overtook { agent, .. } => *overtaken.entry(*agent).or_insert(0) += 1,
+            _ => (),
+        }
+    }
+
+    for stats in all {
+        assert_eq!(
+            stats.cars_overtaken,
+            overtaken.get(&stats.id).copied().unwrap_or(0)
+        );
+        assert!(stats.ticks_alive > 0);
+        assert_eq!(
+            stats.average_speed,
+            stats.distance / stats.ticks_alive as f64
+        );
+    }
+    assert!(overtaken.values().any(|count| *count > 1));
+
+    let json: serde_json::Value = serde_json::from_str(&simulation.all_agent_stats_json()).unwrap();
+    assert_eq!(json.as_array().unwrap().len(), config.cars_count);
+    assert!(json[0]["ticksAlive"].as_u64().is_some());
+    assert!(simulation.agent_stats_json(u32::MAX as usize).is_none());
+}
+
+#[test]
+fn passing_car_is_a_near_miss() {
+    let config = Config::default();
+    let road = Road::new(
+        CAR_CANVAS_WIDTH_DEFAULT / 2.,
+        CAR_CANVAS_WIDTH_DEFAULT * 0.9,
+        config.lanes_count as i32,
+    );
+    // human car stands still, no key is pressed
+    let lane = road.lane_center(1);
+    let agents = Agents::new(vec![Car::human(0, lane, &config)]);
+    let start_y = agents.iter().next().unwrap().y;
+
+    let mut traffic = Traffic::new();
+    // close to the agent, but not touching it
+    traffic.add_vehicle(VehicleType::Car, lane + 35., start_y + 100.);
+
+    let mut telemetry = Telemetry::new(&agents, &road);
+    for _ in 0..200 {
+        traffic.update();
+        telemetry.record(&agents, &road, &traffic, &[]);
+    }
+
+    assert!(traffic.0[0].y < start_y - 100.);
+    let stats = telemetry.get(0).unwrap();
+    assert_eq!(stats.near_misses, 1);
+    assert_eq!(stats.distance, 0.);
+    assert_eq!(stats.lane_changes, 0);
+    assert_eq!(stats.ticks_alive, 200);
+}
@@ -0,0 +1,157 @@
+//! Native tests of the gradient based training
+
+use wasm_self_driving_car::ai::{
+    dataset::Dataset,
+    training::{Loss, Trainer},
+    NeuralNetwork,
+};
+use wasm_self_driving_car::utils::seed_random;
+
+fn xor() -> Dataset {
+    let mut dataset = Dataset::new();
+    dataset.push(vec![0., 0.], vec![0.]);
+    dataset.push(vec![0., 1.], vec![1.]);
+    dataset.push(vec![1., 0.], vec![1.]);
+    dataset.push(vec![1., 1.], vec![0.]);
+    dataset
+}
+
+fn assert_solves_xor(network: &NeuralNetwork) {
+    for sample in xor().samples.iter() {
+        let output = network.forward(&sample.inputs)[0];
+        assert_eq!(
+            (output > 0.5) as u8 as f64,
+            sample.outputs[0],
+            "wrong output {} for {:?}",
+            output,
+            sample.inputs
+        );
+    }
+}
+
+#[test]
+fn forward_returns_output_per_neuron() {
+    seed_random(1);
+    let network = NeuralNetwork::trainable(&[3, 5, 4]);
+    let outputs = network.forward(&[0.1, 0.5, 1.]);
+
+    assert_eq!(outputs.len(), 4);
+    assert!(outputs.iter().all(|o| (0. ..=1.).contains(o)));
+}
+
+#[test]
+fn loss_values() {
+    assert_eq!(Loss::MeanSquaredError.value(&[1., 0.], &[1., 0.]), 0.);
+    assert_eq!(Loss::MeanSquaredError.value(&[1., 0.], &[0., 0.]), 0.5);
+    assert!(Loss::CrossEntropy.value(&[0.99], &[1.]) < Loss::CrossEntropy.value(&[0.5], &[1.]));
+    assert!(Loss::CrossEntropy.value(&[0.], &[1.]).is_finite());
+}
+
+#[test]
+fn adam_learns_xor_with_cross_entropy() {
+    seed_random(42);
+    let dataset = xor();
+    let mut network = NeuralNetwork::trainable(&[2, 4, 1]);
+    let mut trainer = Trainer::adam(0.05, 4).with_loss(Loss::CrossEntropy);
+
+    let before = trainer.evaluate(&network, &dataset);
+    let loss = trainer.train(&mut network, &dataset, 2000).unwrap();
+
+    assert!(loss < before);
+    assert!(loss < 0.1, "loss {} is too high", loss);
+    assert_solves_xor(&network);
+}
+
+#[test]
+fn adam_learns_xor_with_mean_squared_error() {
+    seed_random(7);
+    let dataset = xor();
+    let mut network = NeuralNetwork::trainable(&[2, 4, 1]);
+    let mut trainer = Trainer::adam(0.05, 2);
+
+    let loss = trainer.train(&mut network, &dataset, 2000).unwrap();
+
+    assert!(loss < 0.05, "loss {} is too high", loss);
+    assert_solves_xor(&network);
+}
+
+#[test]
+fn sgd_with_momentum_learns_xor() {
+    seed_random(3);
+    let dataset = xor();
+    let mut network = NeuralNetwork::trainable(&[2, 4, 1]);
+    let mut trainer = Trainer::sgd(0.5, 0.9, 1).with_loss(Loss::CrossEntropy);
+
+    let loss = trainer.train(&mut network, &dataset, 3000).unwrap();
+
+    assert!(loss < 0.1, "loss {} is too high", loss);
+    assert_solves_xor(&network);
+}
+
+#[test]
+fn samples_have_to_fit_brain() {
+    let mut dataset = xor();
+    assert!(dataset.validate(2, 1).is_ok());
+    assert!(dataset.validate(3, 1).is_err());
+
+    dataset.push(vec![1., 1., 1.], vec![0.]);
+    assert!(dataset.validate(2, 1).unwrap_err().contains("sample 4"));
+
+    let config = wasm_self_driving_car::Config::default();
+    let mut recording = Dataset::new();
+    recording.push(vec![0.5; config.rays_count], vec![1., 0., 0., 0.]);
+    let mut simulation = wasm_self_driving_car::Simulation::headless(&config);
+    assert!(simulation.import_recording_json(&recording.to_json()));
+}
+
+#[test]
+fn recurrent_networks_are_not_trained() {
+    use wasm_self_driving_car::{ai::rl::ReinforceTrainer, Config};
+
+    seed_random(4);
+    let mut network = NeuralNetwork::trainable(&[2, 4, 1]).with_memory();
+    let before = network.clone();
+    assert!(Trainer::adam(0.1, 4)
+        .train(&mut network, &xor(), 10)
+        .is_err());
+    assert!(network.parameters().eq(before.parameters()));
+
+    let mut trainer = ReinforceTrainer::new(&Config::default(), 0.01);
+    let recurrent = NeuralNetwork::trainable(&Config::default().neurons_count()).with_memory();
+    assert!(trainer.set_policy(&recurrent).is_err());
+    assert!(!trainer.policy().is_recurrent());
+
+    let error = trainer
+        .set_policy(&NeuralNetwork::trainable(&[3, 4]))
+        .unwrap_err();
+    assert!(error.contains("inputs"), "{}", error);
+}
+
+#[test]
+fn samples_not_fitting_the_network_are_rejected() {
+    seed_random(5);
+    let mut network = NeuralNetwork::trainable(&[2, 4, 1]);
+    let before = network.clone();
+
+    let mut wide = xor();
+    wide.push(vec![0., 1., 1.], vec![1.]);
+    let error = Trainer::adam(0.1, 4)
+        .train(&mut network, &wide, 10)
+        .unwrap_err();
+    assert!(error.contains("sample 4"), "{}", error);
+
+    let mut trainer = Trainer::sgd(0.1, 0., 4);
+    let error = trainer
+        .train_weighted(&mut network, &xor(), &[1., 1.])
+        .unwrap_err();
+    assert!(error.contains("weights"), "{}", error);
+    assert!(network.parameters().eq(before.parameters()));
+}
+
+#[test]
+fn step_networks_are_not_trained() {
+    seed_random(6);
+    let mut network = NeuralNetwork::new(&[2, 4, 1]);
+    let error = network.fit(&xor(), 10, 0.1).unwrap_err();
+    assert!(error.contains("sigmoid"), "{}", error);
+}
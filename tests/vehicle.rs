@@ -0,0 +1,51 @@
+//! Native tests of vehicle types and the traffic mix
+
+use wasm_self_driving_car::replay::CarKind;
+use wasm_self_driving_car::utils::seed_random;
+use wasm_self_driving_car::vehicle::{TrafficMix, VehicleType};
+use wasm_self_driving_car::{Config, Simulation};
+
+#[test]
+fn mix_picks_vehicles_by_weight() {
+    let mix = TrafficMix::new(1., 1., 0., 0., 2.);
+    assert_eq!(mix.pick(0.), VehicleType::Motorcycle);
+    assert_eq!(mix.pick(0.2), VehicleType::Motorcycle);
+    assert_eq!(mix.pick(0.3), VehicleType::Car);
+    assert_eq!(mix.pick(0.5), VehicleType::Bus);
+    assert_eq!(mix.pick(0.99), VehicleType::Bus);
+    // roll of 1 is out of range, but still picks the last type with non zero weight
+    assert_eq!(mix.pick(1.), VehicleType::Bus);
+
+    seed_random(5);
+    let buses = (0..4000)
+        .filter(|_| mix.random() == VehicleType::Bus)
+        .count();
+    assert!((1800..2200).contains(&buses), "{} buses", buses);
+}
+
+#[test]
+fn mix_without_weights_falls_back_to_cars() {
+    let mix = TrafficMix::new(0., 0., 0., 0., 0.);
+    assert_eq!(mix.pick(0.5), VehicleType::Car);
+
+    // negative weights count as zero
+    let mix = TrafficMix::new(-1., 0., 0., -2., 0.);
+    assert_eq!(mix.pick(0.), VehicleType::Car);
+
+    let mix = TrafficMix::new(-1., 0., 0., 3., 0.);
+    assert_eq!(mix.pick(0.), VehicleType::Truck);
+}
+
+#[test]
+fn training_traffic_uses_mix() {
+    let mut simulation = Simulation::headless(&Config::default());
+    simulation.set_traffic_mix(&TrafficMix::new(0., 0., 0., 0., 1.));
+    simulation.training_traffic();
+
+    let traffic = simulation.capture_snapshot().traffic;
+    assert!(!traffic.0.is_empty());
+    assert!(traffic
+        .0
+        .iter()
+        .all(|car| car.pose().kind == CarKind::Traffic(VehicleType::Bus)));
+}
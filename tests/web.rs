@@ -16,8 +16,9 @@ use wasm_self_driving_car::ai::{NeuralNetwork, *};
 #[wasm_bindgen_test]
 fn level_feed_forward_test() {
     let mut level = Level::new(2, 2);
-    level.feed_forward(&vec![1., 2.]);
+    level.feed_forward(&[1., 2.]);
     assert_eq!(level.inputs, vec![1., 2.]);
+    assert_eq!(level.outputs.len(), 2);
 }
 
 #[wasm_bindgen_test]
@@ -32,10 +33,16 @@ fn test_new_nn() {
     assert_eq!(nn.0.len(), 3);
 }
 
-/* #[wasm_bindgen_test]
+#[wasm_bindgen_test]
 fn test_feed_forward_nn() {
     let mut nn = NeuralNetwork::new(&[2, 2, 2]);
-    let outputs = nn.feed_forward(vec![1., 2.]);
-    assert_eq!(outputs, vec![0., 0.]);
+    let outputs = nn.feed_forward(&[1., 2.]).to_vec();
+    assert_eq!(outputs.len(), 2);
+    assert!(outputs.iter().all(|o| *o == 0. || *o == 1.));
+    assert_eq!(outputs, nn.forward(&[1., 2.]));
+
+    let activations = nn.level_activations();
+    assert_eq!(activations.len(), 3);
+    assert_eq!(activations[0], &[1., 2.]);
+    assert_eq!(activations[2], outputs.as_slice());
 }
- */
@@ -9,6 +9,17 @@ export function initForm(document, config) {
   document.getElementById("raysSpread").value = config.raysSpread;
   document.getElementById("hiddenLayersInput").value = config.hiddenLayers;
   document.getElementById("mutationRateInput").value = config.mutationRate;
+  document.getElementById("bicycleModelInput").checked = config.bicycleModel;
+  document.getElementById("speciationThresholdInput").value = config.speciationThreshold;
+  document.getElementById("noveltyWeightInput").value = config.noveltyWeight;
+  document.getElementById("selectionInput").value = config.selection;
+  document.getElementById("selectionSizeInput").value = config.selectionSize;
+  document.getElementById("elitismInput").value = config.elitism;
+  document.getElementById("mutationOperatorInput").value = config.mutationOperator;
+  document.getElementById("mutationProbabilityInput").value = config.mutationProbability;
+  document.getElementById("mutationSigmaInput").value = config.mutationSigma;
+  document.getElementById("plateauPatienceInput").value = config.plateauPatience;
+  document.getElementById("recurrentInput").checked = config.recurrent;
 }
 
 /* export function registerConfigUpdate(document) {
@@ -31,7 +42,7 @@ export function initForm(document, config) {
 } */
 
 export function getConfigFromForm(document) {
-  return new Config(
+  let config = new Config(
     parseInt(document.getElementById("lanesCountInput").value, 10),
     parseInt(document.getElementById("laneIndexInput").value, 10),
     parseInt(document.getElementById("carsCountInput").value, 10),
@@ -44,6 +55,19 @@ export function getConfigFromForm(document) {
       .map((item) => parseInt(item, 10)),
     parseFloat(document.getElementById("mutationRateInput").value)
   );
+  config.bicycleModel = document.getElementById("bicycleModelInput").checked;
+  config.speciationThreshold = parseFloat(document.getElementById("speciationThresholdInput").value);
+  config.noveltyWeight = parseFloat(document.getElementById("noveltyWeightInput").value);
+  config.selection = parseInt(document.getElementById("selectionInput").value, 10);
+  config.selectionSize = parseInt(document.getElementById("selectionSizeInput").value, 10);
+  config.elitism = parseInt(document.getElementById("elitismInput").value, 10);
+  config.mutationOperator = parseInt(document.getElementById("mutationOperatorInput").value, 10);
+  config.mutationProbability = parseFloat(document.getElementById("mutationProbabilityInput").value);
+  config.mutationSigma = parseFloat(document.getElementById("mutationSigmaInput").value);
+  config.plateauPatience = parseInt(document.getElementById("plateauPatienceInput").value, 10);
+  config.recurrent = document.getElementById("recurrentInput").checked;
+
+  return config;
 }
 
 
@@ -1,6 +1,8 @@
 // import * as self_driving_car from "wasm-self-driving-car";
 import {
+  BrainLibrary,
   KeyEvent,
+  ReplayPlayer,
   Simulation,
   Config,
   SimulationState,
@@ -24,18 +26,104 @@ const carCtx = carCanvas.getContext("2d");
 //
 const networkCanvas = document.getElementById("networkCanvas");
 const networkCtx = networkCanvas.getContext("2d");
-networkCanvas.height = window.innerHeight;
+// fitness chart is drawn below the network
+const CHART_HEIGHT = 200;
+networkCanvas.height = window.innerHeight - CHART_HEIGHT;
 networkCanvas.width = window.innerWidth * 0.4;
+
+const chartCanvas = document.getElementById("chartCanvas");
+const chartCtx = chartCanvas.getContext("2d");
+chartCanvas.height = CHART_HEIGHT;
+chartCanvas.width = window.innerWidth * 0.4;
 //
 const startPauseBtn = document.getElementById("startPause");
 startPauseBtn.addEventListener("click", startPause);
 
+const rewindBtn = document.getElementById("rewindBtn");
+rewindBtn.addEventListener("click", rewind);
+
+const stepBackBtn = document.getElementById("stepBackBtn");
+stepBackBtn.addEventListener("click", () => simulation != null && simulation.stepBack());
+
+const stepForwardBtn = document.getElementById("stepForwardBtn");
+stepForwardBtn.addEventListener("click", () => simulation != null && simulation.stepForward());
+
+const speedSelect = document.getElementById("speedSelect");
+speedSelect.addEventListener("change", applySpeed);
+
+const turboChckBox = document.getElementById("turboChckBox");
+turboChckBox.addEventListener("change", applySpeed);
+
+const ticksPerSecond = document.getElementById("ticksPerSecond");
+
 const save_btn = document.getElementById("save");
 save_btn.addEventListener("click", save);
 
 const discard_btn = document.getElementById("discard");
 discard_btn.addEventListener("click", discard);
 
+const exportBtn = document.getElementById("exportBtn");
+exportBtn.addEventListener("click", exportSave);
+
+const importInput = document.getElementById("importInput");
+importInput.addEventListener("change", importSave);
+
+const importBtn = document.getElementById("importBtn");
+importBtn.addEventListener("click", () => importInput.click());
+
+const libraryStoreBtn = document.getElementById("libraryStoreBtn");
+libraryStoreBtn.addEventListener("click", storeToLibrary);
+
+const libraryLoadBtn = document.getElementById("libraryLoadBtn");
+libraryLoadBtn.addEventListener("click", loadFromLibrary);
+
+const storeRecordingBtn = document.getElementById("storeRecordingBtn");
+storeRecordingBtn.addEventListener("click", storeRecording);
+
+const loadRecordingBtn = document.getElementById("loadRecordingBtn");
+loadRecordingBtn.addEventListener("click", loadRecording);
+
+const statsBtn = document.getElementById("statsBtn");
+statsBtn.addEventListener("click", exportStats);
+
+const snapshotBtn = document.getElementById("snapshotBtn");
+snapshotBtn.addEventListener("click", snapshot);
+
+const restoreInput = document.getElementById("restoreInput");
+restoreInput.addEventListener("change", restore);
+
+const restoreBtn = document.getElementById("restoreBtn");
+restoreBtn.addEventListener("click", () => restoreInput.click());
+
+const replayRecordBtn = document.getElementById("replayRecordBtn");
+replayRecordBtn.addEventListener("click", toggleReplayRecording);
+
+const replayExportBtn = document.getElementById("replayExportBtn");
+replayExportBtn.addEventListener("click", exportReplay);
+
+const replayInput = document.getElementById("replayInput");
+replayInput.addEventListener("change", loadReplay);
+
+const replayLoadBtn = document.getElementById("replayLoadBtn");
+replayLoadBtn.addEventListener("click", () => replayInput.click());
+
+const replayPlayBtn = document.getElementById("replayPlayBtn");
+replayPlayBtn.addEventListener("click", playPauseReplay);
+
+const replaySeek = document.getElementById("replaySeek");
+replaySeek.addEventListener("input", () => {
+  if (player != null) {
+    player.seek(parseInt(replaySeek.value, 10));
+  }
+});
+
+const replaySpeed = document.getElementById("replaySpeed");
+replaySpeed.addEventListener("change", () => {
+  if (player != null) {
+    player.speed = parseFloat(replaySpeed.value);
+  }
+});
+
 const runBtn = document.getElementById("runBtn");
 runBtn.addEventListener("click", run);
 
@@ -51,6 +139,46 @@ previousAgentBtn.addEventListener("click", previousAgent);
 const resetFocusBtn = document.getElementById("resetFocusBtn");
 resetFocusBtn.addEventListener("click", resetFocus);
 
+const humanCarBtn = document.getElementById("humanCarBtn");
+humanCarBtn.addEventListener("click", spawnHumanCar);
+
+const KEY_EVENTS = {
+  ArrowUp: [KeyEvent.UpPressed, KeyEvent.UpReleased],
+  ArrowDown: [KeyEvent.DownPressed, KeyEvent.DownReleased],
+  ArrowLeft: [KeyEvent.LeftPressed, KeyEvent.LeftReleased],
+  ArrowRight: [KeyEvent.RightPressed, KeyEvent.RightReleased],
+};
+
+const recordBtn = document.getElementById("recordBtn");
+recordBtn.addEventListener("click", toggleRecording);
+
+const trainFromRecordingBtn = document.getElementById("trainFromRecordingBtn");
+trainFromRecordingBtn.addEventListener("click", trainFromRecording);
+
+const trainReinforceBtn = document.getElementById("trainReinforceBtn");
+trainReinforceBtn.addEventListener("click", trainReinforce);
+
+const nextGenerationBtn = document.getElementById("nextGenerationBtn");
+nextGenerationBtn.addEventListener("click", nextGeneration);
+
+const trainEsBtn = document.getElementById("trainEsBtn");
+trainEsBtn.addEventListener("click", () => trainEvolutionStrategy(ES_GENERATIONS));
+
+const historyCsvBtn = document.getElementById("historyCsvBtn");
+historyCsvBtn.addEventListener("click", () => exportHistory("csv"));
+
+const historyJsonBtn = document.getElementById("historyJsonBtn");
+historyJsonBtn.addEventListener("click", () => exportHistory("json"));
+
+const neatBtn = document.getElementById("neatBtn");
+neatBtn.addEventListener("click", startNeat);
+
+const evolveNeatBtn = document.getElementById("evolveNeatBtn");
+evolveNeatBtn.addEventListener("click", evolveNeat);
+
+document.addEventListener("keydown", (e) => handleKey(e, 0));
+document.addEventListener("keyup", (e) => handleKey(e, 1));
+
 // TESTS SPAWNING
 const easyTestBtn = document.getElementById("easyTestBtn");
 easyTestBtn.addEventListener("click", easyTest);
@@ -67,7 +195,12 @@ trainingTrafficBtn.addEventListener("click", trainingTraffic);
 const drawNetworkChckBox = document.getElementById("drawNetworkChckBox");
 
 let simulation;
+let player;
 let config = Simulation.initConfig(window);
+let library;
+BrainLibrary.open(window)
+  .then((opened) => (library = opened))
+  .catch((e) => console.error("failed to open brain library", e));
 
 initForm(document, config);
 
@@ -88,13 +221,17 @@ tbody.addEventListener("click", function (e) {
 
   if (simulation != null) {
     console.log("focusing agent", cell.innerHTML);
+    if (cell.innerHTML == "you") {
+      simulation.focusHuman();
+      return;
+    }
     simulation.focusAgent(parseInt(cell.innerHTML, 10));
   }
 });
 
 function animate() {
   carCanvas.height = window.innerHeight;
-  networkCanvas.height = window.innerHeight;
+  networkCanvas.height = window.innerHeight - CHART_HEIGHT;
   networkCanvas.width = window.innerWidth * 0.4;
 
   simulation.step(
@@ -105,10 +242,43 @@ function animate() {
   );
 
   updateTable(document, simulation.top10Agents());
+  JSON.parse(simulation.drainEvents()).forEach(handleEvent);
+  ticksPerSecond.innerHTML = Math.round(simulation.ticksPerSecond) + " t/s";
 
   animationFrameId = requestAnimationFrame(animate);
 }
 
+function animateReplay() {
+  carCanvas.height = window.innerHeight;
+
+  player.step(carCtx, carCanvas.height);
+  replaySeek.value = player.position;
+  replayPlayBtn.innerHTML = player.isPlaying ? "⏸️" : "▶️";
+
+  animationFrameId = requestAnimationFrame(animateReplay);
+}
+
+function handleEvent(event) {
+  switch (event.type) {
+    case "crashed":
+      if (event.agent == Simulation.humanAgentId()) {
+        const cause = event.cause == "border" ? "the border" : "car " + event.cause.car;
+        console.log("you crashed into", cause);
+      }
+      break;
+
+    case "scenarioFinished":
+      console.log("scenario", event.name, "finished with", event.survivors, "survivors");
+      break;
+
+    case "generationEnded":
+      console.log("generation", event.generation, "ended, best fitness", event.bestFitness);
+      chartCanvas.width = window.innerWidth * 0.4;
+      simulation.drawFitnessChart(chartCtx);
+      break;
+  }
+}
+
 function save() {
   console.log("saving brain");
   freezeConfig(document);
@@ -122,6 +292,213 @@ function discard() {
   Simulation.discard_brain(window);
 }
 
+function download(blob, name) {
+  const url = URL.createObjectURL(blob);
+  const link = document.createElement("a");
+  link.href = url;
+  link.download = name;
+  link.click();
+  URL.revokeObjectURL(url);
+}
+
+function exportSave() {
+  if (simulation == null) {
+    return;
+  }
+
+  const blob = simulation.exportSaveBlob();
+  if (blob == null) {
+    console.log("focused agent has no brain to export");
+    return;
+  }
+
+  download(blob, "brain.json");
+}
+
+function exportStats() {
+  if (simulation == null) {
+    return;
+  }
+
+  const blob = new Blob([simulation.allAgentStats()], { type: "application/json" });
+  download(blob, "agent-stats-" + simulation.tick + ".json");
+}
+
+function snapshot() {
+  if (simulation == null) {
+    return;
+  }
+
+  const blob = new Blob([simulation.snapshot()], { type: "application/json" });
+  download(blob, "snapshot-" + simulation.tick + ".json");
+}
+
+async function restore() {
+  const file = restoreInput.files[0];
+  if (file == null || simulation == null) {
+    return;
+  }
+
+  const text = await file.text();
+  restoreInput.value = "";
+  if (simulation.restore(text)) {
+    console.log("restored snapshot from", file.name, "tick", simulation.tick);
+  }
+}
+
+function toggleReplayRecording() {
+  if (simulation == null) {
+    return;
+  }
+
+  if (simulation.isRecordingReplay) {
+    simulation.stopReplay();
+    replayRecordBtn.innerHTML = "🎬";
+    console.log("recorded replay frames", simulation.replayFramesCount);
+  } else {
+    simulation.startReplay();
+    replayRecordBtn.innerHTML = "⏹️";
+  }
+}
+
+function exportReplay() {
+  if (simulation == null) {
+    return;
+  }
+
+  const blob = simulation.exportReplayBlob();
+  if (blob == null) {
+    console.log("no replay recorded");
+    return;
+  }
+
+  download(blob, "replay-" + simulation.tick + ".json");
+}
+
+async function loadReplay() {
+  const file = replayInput.files[0];
+  if (file == null) {
+    return;
+  }
+
+  const text = await file.text();
+  replayInput.value = "";
+  const loaded = ReplayPlayer.load(text);
+  if (loaded == null) {
+    return;
+  }
+
+  // replay is drawn instead of the simulation, physics and brains don't run while it plays
+  if (simulation != null) {
+    stop();
+  } else if (animationFrameId) {
+    cancelAnimationFrame(animationFrameId);
+  }
+
+  player = loaded;
+  player.speed = parseFloat(replaySpeed.value);
+  replaySeek.max = player.framesCount - 1;
+  player.play();
+  animateReplay();
+}
+
+function playPauseReplay() {
+  if (player == null) {
+    return;
+  }
+
+  if (player.isPlaying) {
+    player.pause();
+  } else {
+    player.play();
+  }
+}
+
+async function importSave() {
+  const file = importInput.files[0];
+  if (file == null) {
+    return;
+  }
+
+  const text = await file.text();
+  importInput.value = "";
+  if (!Simulation.importSave(window, text)) {
+    return;
+  }
+
+  console.log("imported save from", file.name);
+  config = Simulation.initConfig(window);
+  initForm(document, config);
+  freezeConfig(document);
+  run();
+}
+
+async function storeToLibrary() {
+  if (simulation == null || library == null) {
+    return;
+  }
+
+  const json = simulation.exportSave();
+  if (json == null) {
+    console.log("focused agent has no brain to store");
+    return;
+  }
+
+  const name = prompt("Name of the brain");
+  if (name) {
+    await library.storeSave(name, json);
+    console.log("stored brain", name);
+  }
+}
+
+async function loadFromLibrary() {
+  if (library == null) {
+    return;
+  }
+
+  const names = await library.saveNames();
+  const name = prompt("Load brain: " + names.join(", "));
+  if (!name) {
+    return;
+  }
+
+  const json = await library.loadSave(name);
+  if (json == null || !Simulation.importSave(window, json)) {
+    console.log("failed to load brain", name);
+    return;
+  }
+
+  config = Simulation.initConfig(window);
+  initForm(document, config);
+  freezeConfig(document);
+  run();
+}
+
+async function storeRecording() {
+  if (simulation == null || library == null || simulation.recordedSamplesCount() == 0) {
+    return;
+  }
+
+  const name = prompt("Name of the recording");
+  if (name) {
+    await library.storeDataset(name, simulation.exportRecordingJson());
+    console.log("stored recording", name);
+  }
+}
+
+async function loadRecording() {
+  if (simulation == null || library == null) {
+    return;
+  }
+
+  const names = await library.datasetNames();
+  const name = prompt("Load recording: " + names.join(", "));
+  const json = name ? await library.loadDataset(name) : null;
+  if (json != null && simulation.importRecordingJson(json)) {
+    console.log("loaded recording", name, "samples", simulation.recordedSamplesCount());
+  }
+}
+
 function startPause() {
   if (simulation == null) {
     console.log("simulation is null, doing nothing");
@@ -142,6 +519,30 @@ function startPause() {
   }
 }
 
+// milliseconds of a frame spent simulating in turbo mode, rest is left for drawing
+const TURBO_BUDGET = 12;
+
+function applySpeed() {
+  if (simulation == null) {
+    return;
+  }
+
+  simulation.speed = parseFloat(speedSelect.value);
+  simulation.turbo = turboChckBox.checked ? TURBO_BUDGET : undefined;
+}
+
+// 5 seconds at 60 ticks per second
+const REWIND_TICKS = 300;
+
+function rewind() {
+  if (simulation == null) {
+    return;
+  }
+
+  const rewound = simulation.rewind(REWIND_TICKS);
+  console.log("rewound by", rewound, "ticks to tick", simulation.tick);
+}
+
 function stop() {
   if (animationFrameId) {
     cancelAnimationFrame(animationFrameId);
@@ -152,9 +553,10 @@ function stop() {
 }
 
 function run() {
-  if (simulation != null) {
+  if (animationFrameId) {
     cancelAnimationFrame(animationFrameId);
   }
+  player = null;
 
   simulation = new Simulation(
     carCanvas.width,
@@ -162,6 +564,7 @@ function run() {
     getConfigFromForm(document)
   );
   //simulation.addTestTraffic();
+  applySpeed();
   simulation.run();
   animate();
   return;
@@ -203,14 +606,135 @@ function updateTable(document, rankings) {
 
   for (let i = 0; i < rankings.length; i++) {
     table.rows[i].cells[0].innerHTML = i + 1 + ".";
-    table.rows[i].cells[1].innerHTML = rankings[i];
+    table.rows[i].cells[1].innerHTML =
+      rankings[i] == Simulation.humanAgentId() ? "you" : rankings[i];
+
+    const stats = simulation.agentStats(rankings[i]);
+    table.rows[i].title =
+      stats == null ? "" : statsSummary(JSON.parse(stats));
   }
 }
 
+function statsSummary(stats) {
+  return [
+    "distance " + Math.round(stats.distance),
+    "alive " + stats.ticksAlive + " ticks",
+    "avg speed " + stats.averageSpeed.toFixed(2),
+    "lane changes " + stats.laneChanges,
+    "near misses " + stats.nearMisses,
+    "overtaken " + stats.carsOvertaken,
+  ].join("\n");
+}
+
 function resetFocus() {
   simulation.resetFocus();
 }
 
+function spawnHumanCar() {
+  if (simulation == null) {
+    return;
+  }
+  simulation.spawnHumanCar();
+  simulation.focusHuman();
+}
+
+function toggleRecording() {
+  if (simulation == null) {
+    return;
+  }
+
+  if (simulation.isRecording) {
+    simulation.stopRecording();
+    recordBtn.innerHTML = "Record";
+    console.log("recorded samples", simulation.recordedSamplesCount());
+  } else {
+    simulation.startRecording();
+    recordBtn.innerHTML = "Stop Recording";
+  }
+}
+
+function trainFromRecording() {
+  if (simulation == null || simulation.recordedSamplesCount() == 0) {
+    console.log("nothing recorded, nothing to train on");
+    return;
+  }
+  const loss = simulation.trainFromRecording(200, 0.01);
+  console.log("trained brain from recording, loss", loss);
+}
+
+function trainReinforce() {
+  if (simulation == null) {
+    return;
+  }
+  const fitness = simulation.trainReinforce(50, 0.01);
+  console.log("trained brain with reinforcement learning, mean fitness", fitness);
+}
+
+function nextGeneration() {
+  if (simulation == null) {
+    return;
+  }
+  const stats = simulation.nextGeneration();
+  if (stats != null) {
+    console.log(
+      "generation", stats.generation,
+      "best", stats.bestFitness,
+      "mean", stats.meanFitness,
+      "diversity", stats.diversity,
+      "species", stats.species,
+      "novel states", stats.novelStates,
+      "mutation scale", stats.mutationScale
+    );
+  }
+}
+
+function exportHistory(format) {
+  if (simulation == null) {
+    return;
+  }
+
+  const [text, type] =
+    format == "csv"
+      ? [simulation.historyCsv(), "text/csv"]
+      : [simulation.historyJson(), "application/json"];
+  download(new Blob([text], { type }), "history." + format);
+}
+
+const ES_GENERATIONS = 5;
+
+// generations are trained one at a time with a break in between, so the page does not freeze
+function trainEvolutionStrategy(remaining) {
+  if (simulation == null || remaining <= 0) {
+    return;
+  }
+  const fitness = simulation.trainEvolutionStrategy(1);
+  console.log("trained brain with evolution strategy, mean brain fitness", fitness);
+  setTimeout(() => trainEvolutionStrategy(remaining - 1), 0);
+}
+
+function startNeat() {
+  if (simulation == null) {
+    return;
+  }
+  simulation.startNeat();
+}
+
+function evolveNeat() {
+  if (simulation == null) {
+    return;
+  }
+  const generation = simulation.evolveNeat();
+  console.log("NEAT generation", generation, "species", simulation.neatSpeciesCount());
+}
+
+function handleKey(e, released) {
+  if (simulation == null || !(e.key in KEY_EVENTS)) {
+    return;
+  }
+  e.preventDefault();
+  simulation.handleKeyInput(KEY_EVENTS[e.key][released]);
+}
+
 const EASY = 1;
 const MEDIUM = 0.75;
 const HARD = 0.5;